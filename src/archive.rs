@@ -0,0 +1,286 @@
+use crate::check;
+use crate::config;
+use crate::metadata;
+
+use std::fs;
+use std::io::{Read, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::path::PathBuf;
+use base64::Engine;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+const SNAPSHOT_EXTENSION: &str = "html.gz";
+
+/// Compressed snapshots of pages stored by the SHA-256 of their contents, so archiving an unchanged
+/// page again does not store it twice. Which SchWiMark a snapshot belongs to is kept in the database.
+pub struct Archive {
+	directory: PathBuf,
+	/// where snapshots are decompressed to be opened, only accessible by the user
+	extract_directory: PathBuf,
+}
+
+impl Archive {
+	pub fn new(config: &config::Config) -> Archive {
+		let directory: PathBuf = match config.archive_directory.as_os_str().is_empty() {
+			true => {
+				config.database_file
+					.parent()
+					.map(|p| p.join("archive"))
+					.unwrap_or(PathBuf::from("archive"))
+			}
+			false => { config.archive_directory.clone() }
+		};
+
+		// SAFETY: getuid has no preconditions and cannot fail.
+		let uid: u32 = unsafe { libc::getuid() };
+		let extract_directory: PathBuf = std::env::temp_dir().join(format!("schwimark-{}", uid));
+
+		Archive { directory, extract_directory }
+	}
+
+	/// Downloads the page at `url` and stores it, with `assets` images, stylesheets and scripts are
+	/// embedded as data urls so the snapshot is a single file. Returns the hash of the snapshot.
+	pub fn snapshot(&self, client: &dyn check::HttpClient, url: &str, assets: bool) -> Result<String, String> {
+		let followed: check::Followed = check::follow(client, check::Method::Get, url)?;
+		if followed.response.status >= 400 {
+			return Err(format!("the server responded with {}", followed.response.status));
+		}
+
+		let page: Vec<u8> = match assets {
+			true => {
+				let page_url: &str = followed.redirect.as_deref().unwrap_or(url);
+				inline_assets(client, page_url, &String::from_utf8_lossy(&followed.response.body)).into_bytes()
+			}
+			false => { followed.response.body }
+		};
+		self.store(&page)
+	}
+
+	fn store(&self, page: &[u8]) -> Result<String, String> {
+		let hash: String = ring::digest::digest(&ring::digest::SHA256, page)
+			.as_ref()
+			.iter()
+			.map(|b| format!("{:02x}", b))
+			.collect();
+		let path: PathBuf = self.path(&hash);
+		if path.exists() { return Ok(hash); }
+
+		fs::create_dir_all(&self.directory)
+			.map_err(|e| format!("failed to create the archive directory \"{}\": {}", self.directory.display(), e))?;
+		let mut encoder: GzEncoder<Vec<u8>> = GzEncoder::new(vec![], Compression::best());
+		encoder.write_all(page).map_err(|e| e.to_string())?;
+		let compressed: Vec<u8> = encoder.finish().map_err(|e| e.to_string())?;
+
+		// written under a temporary name first so an interrupted write never leaves a broken snapshot
+		let partial_path: PathBuf = path.with_extension("partial");
+		fs::write(&partial_path, compressed)
+			.and_then(|_| fs::rename(&partial_path, &path))
+			.map_err(|e| format!("failed to write the snapshot \"{}\": {}", path.display(), e))?;
+		Ok(hash)
+	}
+
+	/// Decompresses a snapshot into the temporary directory so it can be opened in a browser.
+	/// Every snapshot has one file there, which is reused when it is opened again and is kept, as the
+	/// browser may read it after schwimark exited.
+	pub fn extract(&self, hash: &str) -> Result<PathBuf, String> {
+		self.create_extract_directory()?;
+		let extracted_path: PathBuf = self.extract_directory.join(format!("{}.html", hash));
+		// the file is only ever created by renaming a complete one, so an existing file can be used as is
+		if extracted_path.is_file() { return Ok(extracted_path); }
+
+		let path: PathBuf = self.path(hash);
+		let compressed: fs::File = fs::File::open(&path)
+			.map_err(|e| format!("failed to read the snapshot \"{}\": {}", path.display(), e))?;
+		let mut page: Vec<u8> = vec![];
+		GzDecoder::new(compressed)
+			.read_to_end(&mut page)
+			.map_err(|e| format!("failed to decompress the snapshot \"{}\": {}", path.display(), e))?;
+
+		let mut extracted: tempfile::NamedTempFile = tempfile::NamedTempFile::new_in(&self.extract_directory)
+			.map_err(|e| format!("failed to create a temporary file: {}", e))?;
+		extracted.write_all(&page).map_err(|e| format!("failed to write \"{}\": {}", extracted.path().display(), e))?;
+		extracted.persist(&extracted_path).map_err(|e| format!("failed to write \"{}\": {}", extracted_path.display(), e))?;
+		Ok(extracted_path)
+	}
+
+	/// Creates the directory of the extracted snapshots. As it is in the shared temporary directory it must
+	/// belong to the user and be inaccessible to others, otherwise someone else could replace the pages.
+	fn create_extract_directory(&self) -> Result<(), String> {
+		let directory: &PathBuf = &self.extract_directory;
+		match fs::DirBuilder::new().mode(0o700).create(directory) {
+			Ok(_) => {}
+			Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+			Err(e) => { return Err(format!("failed to create \"{}\": {}", directory.display(), e)); }
+		}
+
+		let metadata: fs::Metadata = fs::symlink_metadata(directory)
+			.map_err(|e| format!("failed to read \"{}\": {}", directory.display(), e))?;
+		// SAFETY: getuid has no preconditions and cannot fail.
+		let uid: u32 = unsafe { libc::getuid() };
+		if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+			return Err(format!("\"{}\" is not a directory only accessible by you", directory.display()));
+		}
+		Ok(())
+	}
+
+	fn path(&self, hash: &str) -> PathBuf {
+		self.directory.join(format!("{}.{}", hash, SNAPSHOT_EXTENSION))
+	}
+}
+
+/// Replaces the urls of images, stylesheets, icons and scripts with data urls of their contents.
+/// Assets which cannot be downloaded keep their url.
+fn inline_assets(client: &dyn check::HttpClient, page_url: &str, html: &str) -> String {
+	let lowercase: String = html.to_ascii_lowercase();
+	let mut inlined: String = String::with_capacity(html.len());
+	let mut position: usize = 0;
+	let mut search: usize = 0;
+
+	while let Some(start) = lowercase[search..].find('<').map(|i| search + i) {
+		search = start + 1;
+		let name_end: usize = lowercase[start + 1..]
+			.find(|c: char| !c.is_ascii_alphanumeric())
+			.map(|i| start + 1 + i)
+			.unwrap_or(html.len());
+		let attribute: &str = match &lowercase[start + 1..name_end] {
+			"img" | "script" => { "src" }
+			"link" => { "href" }
+			_ => { continue; }
+		};
+		let end: usize = match html[name_end..].find('>') {
+			Some(i) => { name_end + i }
+			None => { break; }
+		};
+
+		let mut attributes: Vec<(String, String)> = metadata::parse_attributes(&html[name_end..end]);
+		let is_asset: bool = attribute == "src" || attributes.iter().any(|(n, v)| {
+			n == "rel" && v.to_ascii_lowercase().split_whitespace().any(|r| r == "stylesheet" || r == "icon")
+		});
+		let value: &mut String = match attributes.iter_mut().find(|(n, _)| n == attribute) {
+			Some((_, v)) if is_asset && !v.is_empty() && !v.starts_with("data:") => { v }
+			_ => { continue; }
+		};
+
+		let asset_url: String = check::resolve_location(page_url, value);
+		match data_url(client, &asset_url) {
+			Ok(d) => { *value = d; }
+			Err(e) => {
+				eprintln!("could not embed \"{}\": {}", asset_url, e);
+				continue;
+			}
+		}
+
+		inlined.push_str(&html[position..start]);
+		inlined.push('<');
+		inlined.push_str(&html[start + 1..name_end]);
+		for (name, value) in attributes {
+			inlined.push_str(&format!(" {}=\"{}\"", name, value.replace('&', "&amp;").replace('"', "&quot;")));
+		}
+		inlined.push('>');
+		position = end + 1;
+		search = position;
+	}
+	inlined.push_str(&html[position..]);
+	inlined
+}
+
+fn data_url(client: &dyn check::HttpClient, url: &str) -> Result<String, String> {
+	let followed: check::Followed = check::follow(client, check::Method::Get, url)?;
+	if followed.response.status >= 400 {
+		return Err(format!("the server responded with {}", followed.response.status));
+	}
+	let mime: String = followed.response.content_type
+		.and_then(|t| t.split(';').next().map(|t| t.trim().to_string()))
+		.filter(|t| !t.is_empty())
+		.unwrap_or("application/octet-stream".to_string());
+	Ok(format!("data:{};base64,{}", mime, base64::engine::general_purpose::STANDARD.encode(&followed.response.body)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::os::unix::fs::PermissionsExt;
+
+	/// Answers every request from a table of `url -> (content type, body)`, unknown urls get a 404.
+	struct StubClient {
+		responses: Vec<(&'static str, Option<&'static str>, &'static [u8])>,
+	}
+
+	impl check::HttpClient for StubClient {
+		fn request(&self, _method: check::Method, url: &str) -> Result<check::Response, String> {
+			let response = self.responses.iter().find(|(u, _, _)| *u == url);
+			Ok(check::Response {
+				status: if response.is_some() { 200 } else { 404 },
+				location: None,
+				content_type: response.and_then(|(_, t, _)| t.map(|t| t.to_string())),
+				body: response.map(|(_, _, b)| b.to_vec()).unwrap_or_default(),
+			})
+		}
+	}
+
+	fn archive(directory: &std::path::Path) -> Archive {
+		let mut config: config::Config = config::Config::default();
+		config.archive_directory = directory.join("archive");
+		let mut archive: Archive = Archive::new(&config);
+		archive.extract_directory = directory.join("extracted");
+		archive
+	}
+
+	#[test]
+	fn snapshots_are_extracted_once_into_private_files() {
+		let directory: tempfile::TempDir = tempfile::tempdir().unwrap();
+		let archive: Archive = archive(directory.path());
+		let hash: String = archive.store(b"<html>page</html>").unwrap();
+		assert_eq!(archive.store(b"<html>page</html>").unwrap(), hash);
+
+		let extracted: PathBuf = archive.extract(&hash).unwrap();
+		assert_eq!(archive.extract(&hash).unwrap(), extracted);
+		assert_eq!(fs::read(&extracted).unwrap(), b"<html>page</html>");
+		assert_eq!(fs::metadata(&extracted).unwrap().permissions().mode() & 0o777, 0o600);
+		assert_eq!(fs::metadata(&archive.extract_directory).unwrap().permissions().mode() & 0o777, 0o700);
+		assert_eq!(fs::read_dir(&archive.extract_directory).unwrap().count(), 1);
+		assert!(archive.extract("0000").is_err());
+	}
+
+	#[test]
+	fn extracting_refuses_a_directory_others_can_access() {
+		let directory: tempfile::TempDir = tempfile::tempdir().unwrap();
+		let archive: Archive = archive(directory.path());
+		let hash: String = archive.store(b"<html>page</html>").unwrap();
+		fs::create_dir(&archive.extract_directory).unwrap();
+		fs::set_permissions(&archive.extract_directory, fs::Permissions::from_mode(0o777)).unwrap();
+		assert!(archive.extract(&hash).is_err());
+
+		fs::remove_dir(&archive.extract_directory).unwrap();
+		std::os::unix::fs::symlink(directory.path(), &archive.extract_directory).unwrap();
+		assert!(archive.extract(&hash).is_err());
+	}
+
+	#[test]
+	fn assets_are_inlined_as_data_urls() {
+		let client: StubClient = StubClient { responses: vec![
+			("https://example.com/static/logo.png", Some("image/png; charset=binary"), b"png"),
+			("https://example.com/style.css", Some("text/css"), b"body{}"),
+			("https://cdn.example.com/app.js", None, b"js"),
+		] };
+		let html: &str = "<html><head><link rel=\"Stylesheet\" href=\"/style.css\"><link rel=\"canonical\" href=\"/docs\">\
+			<SCRIPT src='https://cdn.example.com/app.js'></SCRIPT></head>\
+			<body><img alt=\"a &amp; b\" src=\"static/logo.png\"><img src=\"missing.png\"><img src=\"data:,x\"></body></html>";
+
+		assert_eq!(
+			inline_assets(&client, "https://example.com/page", html),
+			"<html><head><link rel=\"Stylesheet\" href=\"data:text/css;base64,Ym9keXt9\"><link rel=\"canonical\" href=\"/docs\">\
+			<SCRIPT src=\"data:application/octet-stream;base64,anM=\"></SCRIPT></head>\
+			<body><img alt=\"a &amp; b\" src=\"data:image/png;base64,cG5n\"><img src=\"missing.png\"><img src=\"data:,x\"></body></html>",
+		);
+	}
+
+	#[test]
+	fn data_url_fails_for_error_responses() {
+		let client: StubClient = StubClient { responses: vec![("https://example.com/a.txt", Some(""), b"a")] };
+		assert_eq!(data_url(&client, "https://example.com/a.txt").unwrap(), "data:application/octet-stream;base64,YQ==");
+		assert!(data_url(&client, "https://example.com/missing.txt").is_err());
+	}
+}
@@ -0,0 +1,272 @@
+use crate::config;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use rusqlite::{Connection, MAIN_DB};
+
+const BACKUP_PREFIX: &str = "schwimark-";
+const BACKUP_EXTENSION: &str = "db";
+
+/// Rotating backups of the database created with the SQLite online backup API.
+/// Backups are named `schwimark-<UTC timestamp>-<reason>.db`.
+pub struct Backups {
+	directory: PathBuf,
+	retention: usize,
+	interval: Duration,
+}
+
+pub struct BackupFile {
+	pub path: PathBuf,
+	pub name: String,
+	pub size: u64,
+	modified: SystemTime,
+}
+
+impl Backups {
+	pub fn new(config: &config::Config) -> Backups {
+		let directory: PathBuf = match config.backup_directory.as_os_str().is_empty() {
+			true => {
+				config.database_file
+					.parent()
+					.map(|p| p.join("backups"))
+					.unwrap_or(PathBuf::from("backups"))
+			}
+			false => { config.backup_directory.clone() }
+		};
+
+		Backups {
+			directory,
+			retention: config.backup_retention,
+			interval: Duration::from_secs(config.backup_interval_hours * 60 * 60),
+		}
+	}
+
+	/// Writes a backup of `database` and removes the oldest backups exceeding the retention.
+	/// Does nothing if the retention is 0.
+	pub fn create(&self, database: &Connection, reason: &str) -> Result<Option<PathBuf>, String> {
+		if self.retention == 0 { return Ok(None); }
+		let backup_path: PathBuf = self.write(database, reason)?;
+		self.prune()?;
+		Ok(Some(backup_path))
+	}
+
+	fn write(&self, database: &Connection, reason: &str) -> Result<PathBuf, String> {
+		fs::create_dir_all(&self.directory)
+			.map_err(|e| format!("failed to create the backup directory \"{}\": {}", self.directory.display(), e))?;
+
+		let timestamp: String = database
+			.query_row("SELECT strftime('%Y%m%d-%H%M%S', 'now')", [], |row| row.get::<usize, String>(0))
+			.map_err(|e| e.to_string())?;
+		let mut backup_path: PathBuf = self.directory.join(format!("{}{}-{}.{}", BACKUP_PREFIX, timestamp, reason, BACKUP_EXTENSION));
+		let mut counter: usize = 1;
+		while backup_path.exists() {
+			backup_path = self.directory.join(format!("{}{}-{}-{}.{}", BACKUP_PREFIX, timestamp, reason, counter, BACKUP_EXTENSION));
+			counter += 1;
+		}
+
+		database.backup(MAIN_DB, &backup_path, None)
+			.map_err(|e| format!("failed to write the backup \"{}\": {}", backup_path.display(), e))?;
+		Ok(backup_path)
+	}
+
+	/// Creates a backup if the newest one is older than the configured interval (0 disables this).
+	pub fn create_scheduled(&self, database: &Connection) -> Result<Option<PathBuf>, String> {
+		if self.interval.is_zero() { return Ok(None); }
+		let newest_age: Option<Duration> = self.list()?
+			.first()
+			.and_then(|b| SystemTime::now().duration_since(b.modified).ok());
+
+		match newest_age {
+			Some(age) if age < self.interval => { Ok(None) }
+			_ => { self.create(database, "scheduled") }
+		}
+	}
+
+	/// Lists the backups newest first.
+	pub fn list(&self) -> Result<Vec<BackupFile>, String> {
+		let entries = match fs::read_dir(&self.directory) {
+			Ok(e) => { e }
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => { return Ok(vec![]); }
+			Err(e) => { return Err(format!("failed to read the backup directory \"{}\": {}", self.directory.display(), e)); }
+		};
+
+		let mut backups: Vec<BackupFile> = entries
+			.filter_map(|e| e.ok())
+			.filter(|e| is_backup(&e.path()))
+			.map(|e| BackupFile {
+				path: e.path(),
+				name: e.file_name().to_string_lossy().to_string(),
+				size: e.metadata().map(|m| m.len()).unwrap_or(0),
+				modified: e.metadata().and_then(|m| m.modified()).unwrap_or(SystemTime::UNIX_EPOCH),
+			})
+			.collect();
+		backups.sort_by(|a, b| b.modified.cmp(&a.modified).then(b.name.cmp(&a.name)));
+		Ok(backups)
+	}
+
+	/// Finds a backup by its position in `list` (starting at 1), its file name or `latest`.
+	pub fn find(&self, backup: &str) -> Result<BackupFile, String> {
+		let mut backups: Vec<BackupFile> = self.list()?;
+		let position: Option<usize> = match backup {
+			"latest" => { if backups.is_empty() { None } else { Some(0) } }
+			_ => {
+				backup.parse::<usize>().ok()
+					.filter(|i| *i >= 1 && *i <= backups.len())
+					.map(|i| i - 1)
+					.or(backups.iter().position(|b| b.name == backup))
+			}
+		};
+
+		match position {
+			Some(p) => { Ok(backups.swap_remove(p)) }
+			None => { Err(format!("no backup matches \"{}\"", backup)) }
+		}
+	}
+
+	/// Replaces the contents of `database` with the given backup after backing up the current state
+	/// (unless the retention is 0). Pruning waits for the restore, so it cannot remove the backup being restored.
+	pub fn restore(&self, database: &mut Connection, backup: &BackupFile) -> Result<(), String> {
+		if self.retention > 0 { self.write(database, "pre-restore")?; }
+		database.restore(MAIN_DB, &backup.path, None::<fn(rusqlite::backup::Progress)>)
+			.map_err(|e| format!("failed to restore the backup \"{}\": {}", backup.path.display(), e))?;
+		self.prune()
+	}
+
+	fn prune(&self) -> Result<(), String> {
+		if self.retention == 0 { return Ok(()); }
+		for old in self.list()?.into_iter().skip(self.retention) {
+			fs::remove_file(&old.path)
+				.map_err(|e| format!("failed to remove the old backup \"{}\": {}", old.path.display(), e))?;
+		}
+		Ok(())
+	}
+}
+
+fn is_backup(path: &Path) -> bool {
+	let name: String = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+	name.starts_with(BACKUP_PREFIX) && path.extension().is_some_and(|e| e == BACKUP_EXTENSION)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn backups(directory: &Path, retention: usize, interval_hours: u64) -> Backups {
+		Backups { directory: directory.to_path_buf(), retention, interval: Duration::from_secs(interval_hours * 60 * 60) }
+	}
+
+	fn database(marks: i64) -> Connection {
+		let database: Connection = Connection::open_in_memory().unwrap();
+		database.execute_batch("CREATE TABLE schwimark (markid INTEGER PRIMARY KEY)").unwrap();
+		for id in 1..=marks {
+			database.execute("INSERT INTO schwimark (markid) VALUES (?1)", [id]).unwrap();
+		}
+		database
+	}
+
+	fn marks(database: &Connection) -> i64 {
+		database.query_row("SELECT COUNT(*) FROM schwimark", [], |row| row.get::<usize, i64>(0)).unwrap()
+	}
+
+	/// Backups created within the same second would otherwise only be ordered by name.
+	fn set_age(backup: &Path, hours: u64) {
+		let modified: SystemTime = SystemTime::now() - Duration::from_secs(hours * 60 * 60);
+		fs::File::options().write(true).open(backup).unwrap().set_modified(modified).unwrap();
+	}
+
+	#[test]
+	fn create_keeps_only_the_newest_backups() {
+		let directory: tempfile::TempDir = tempfile::tempdir().unwrap();
+		let backups: Backups = backups(directory.path(), 2, 0);
+		let database: Connection = database(1);
+
+		let oldest: PathBuf = backups.create(&database, "first").unwrap().unwrap();
+		set_age(&oldest, 3);
+		let older: PathBuf = backups.create(&database, "second").unwrap().unwrap();
+		set_age(&older, 2);
+		let newest: PathBuf = backups.create(&database, "third").unwrap().unwrap();
+
+		let names: Vec<String> = backups.list().unwrap().into_iter().map(|b| b.name).collect();
+		assert_eq!(names.len(), 2);
+		assert!(names[0].ends_with("-third.db") && names[1].ends_with("-second.db"));
+		assert!(!oldest.exists() && newest.exists());
+		fs::write(directory.path().join("notes.txt"), "not a backup").unwrap();
+		assert_eq!(backups.list().unwrap().len(), 2);
+	}
+
+	#[test]
+	fn create_does_nothing_without_retention() {
+		let directory: tempfile::TempDir = tempfile::tempdir().unwrap();
+		let backups: Backups = backups(&directory.path().join("backups"), 0, 1);
+		assert!(backups.create(&database(1), "manual").unwrap().is_none());
+		assert!(backups.create_scheduled(&database(1)).unwrap().is_none());
+		assert!(!directory.path().join("backups").exists());
+	}
+
+	#[test]
+	fn find_by_position_name_or_latest() {
+		let directory: tempfile::TempDir = tempfile::tempdir().unwrap();
+		let backups: Backups = backups(directory.path(), 5, 0);
+		assert!(backups.find("latest").is_err());
+
+		let old: PathBuf = backups.create(&database(1), "old").unwrap().unwrap();
+		set_age(&old, 1);
+		let new: PathBuf = backups.create(&database(1), "new").unwrap().unwrap();
+		let old_name: String = old.file_name().unwrap().to_string_lossy().to_string();
+
+		assert_eq!(backups.find("latest").unwrap().path, new);
+		assert_eq!(backups.find("1").unwrap().path, new);
+		assert_eq!(backups.find("2").unwrap().path, old);
+		assert_eq!(backups.find(&old_name).unwrap().path, old);
+		assert!(backups.find("0").is_err());
+		assert!(backups.find("3").is_err());
+		assert!(backups.find("schwimark-missing.db").is_err());
+	}
+
+	#[test]
+	fn restore_backs_up_the_current_state_first() {
+		let directory: tempfile::TempDir = tempfile::tempdir().unwrap();
+		let backups: Backups = backups(directory.path(), 1, 0);
+		let mut database: Connection = database(1);
+		let backup: PathBuf = backups.create(&database, "manual").unwrap().unwrap();
+		set_age(&backup, 1);
+		database.execute("INSERT INTO schwimark (markid) VALUES (2)", []).unwrap();
+
+		// the pre-restore backup exceeds the retention, but the restored backup is only removed afterwards
+		backups.restore(&mut database, &backups.find("latest").unwrap()).unwrap();
+		assert_eq!(marks(&database), 1);
+		let remaining: Vec<BackupFile> = backups.list().unwrap();
+		assert_eq!(remaining.len(), 1);
+		assert!(remaining[0].name.ends_with("-pre-restore.db"));
+		assert_eq!(marks(&Connection::open(&remaining[0].path).unwrap()), 2);
+	}
+
+	#[test]
+	fn restore_writes_no_backup_without_retention() {
+		let directory: tempfile::TempDir = tempfile::tempdir().unwrap();
+		let backup: PathBuf = backups(directory.path(), 1, 0).create(&database(1), "manual").unwrap().unwrap();
+		let backups: Backups = backups(directory.path(), 0, 0);
+		let mut database: Connection = database(3);
+
+		backups.restore(&mut database, &backups.find("latest").unwrap()).unwrap();
+		assert_eq!(marks(&database), 1);
+		assert_eq!(backups.list().unwrap().into_iter().map(|b| b.path).collect::<Vec<PathBuf>>(), [backup]);
+	}
+
+	#[test]
+	fn create_scheduled_waits_for_the_interval() {
+		let directory: tempfile::TempDir = tempfile::tempdir().unwrap();
+		let database: Connection = database(1);
+		assert!(backups(directory.path(), 5, 0).create_scheduled(&database).unwrap().is_none());
+
+		let backups: Backups = backups(directory.path(), 5, 24);
+		let first: PathBuf = backups.create_scheduled(&database).unwrap().unwrap();
+		assert!(first.to_string_lossy().ends_with("-scheduled.db"));
+		assert!(backups.create_scheduled(&database).unwrap().is_none());
+
+		set_age(&first, 25);
+		assert!(backups.create_scheduled(&database).unwrap().is_some());
+		assert_eq!(backups.list().unwrap().len(), 2);
+	}
+}
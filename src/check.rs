@@ -0,0 +1,354 @@
+use crate::config;
+use crate::paths;
+use crate::sql;
+
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+const MAX_REDIRECTS: usize = 10;
+/// at most this many bytes of a body are read
+const MAX_BODY_SIZE: u64 = 16 * 1024 * 1024;
+const USER_AGENT: &str = concat!("schwimark/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Method {
+	Head,
+	Get,
+}
+
+/// Status, `Location` and `Content-Type` headers and body of a response, the body is only read for GET requests.
+pub struct Response {
+	pub status: u16,
+	pub location: Option<String>,
+	pub content_type: Option<String>,
+	pub body: Vec<u8>,
+}
+
+/// Sends a single request without following redirects.
+/// Implemented with ureq by `UreqClient`, tests can put a local stand-in server behind it.
+pub trait HttpClient: Sync {
+	fn request(&self, method: Method, url: &str) -> Result<Response, String>;
+}
+
+pub struct UreqClient {
+	agent: ureq::Agent,
+}
+
+impl UreqClient {
+	pub fn new(timeout: Duration) -> UreqClient {
+		let agent: ureq::Agent = ureq::Agent::config_builder()
+			.timeout_global(Some(timeout))
+			.max_redirects(0)
+			.http_status_as_error(false)
+			.user_agent(USER_AGENT)
+			.build()
+			.into();
+		UreqClient { agent }
+	}
+}
+
+impl HttpClient for UreqClient {
+	fn request(&self, method: Method, url: &str) -> Result<Response, String> {
+		let mut response = match method {
+			Method::Head => { self.agent.head(url).call() }
+			Method::Get => { self.agent.get(url).call() }
+		}.map_err(|e| e.to_string())?;
+
+		let mut body: Vec<u8> = vec![];
+		if method == Method::Get {
+			response.body_mut().as_reader().take(MAX_BODY_SIZE).read_to_end(&mut body).map_err(|e| e.to_string())?;
+		}
+		let header = |name: &str| -> Option<String> {
+			response.headers().get(name).and_then(|v| v.to_str().ok()).map(|v| v.to_string())
+		};
+		Ok(Response {
+			status: response.status().as_u16(),
+			location: header("location"),
+			content_type: header("content-type"),
+			body,
+		})
+	}
+}
+
+/// Checks the urls/paths of SchWiMarks, running at most `concurrency` requests at once.
+pub struct Checker<'a> {
+	client: &'a dyn HttpClient,
+	concurrency: usize,
+	base_directory: PathBuf,
+}
+
+impl<'a> Checker<'a> {
+	pub fn new(client: &'a dyn HttpClient, config: &config::Config) -> Checker<'a> {
+		Checker {
+			client,
+			concurrency: config.check_concurrency.max(1),
+			base_directory: config.path_base_directory.clone(),
+		}
+	}
+
+	/// Returns the result for every SchWiMark in the order of `marks`, None for urls with a scheme
+	/// that cannot be checked (e.g. mailto:).
+	pub fn check(&self, marks: &[sql::SchWiMark]) -> Vec<Option<sql::LinkCheck>> {
+		let next: AtomicUsize = AtomicUsize::new(0);
+		let mut results: Vec<(usize, Option<sql::LinkCheck>)> = thread::scope(|scope| {
+			let workers: Vec<_> = (0..self.concurrency.min(marks.len()))
+				.map(|_| scope.spawn(|| {
+					let mut checked: Vec<(usize, Option<sql::LinkCheck>)> = vec![];
+					loop {
+						let i: usize = next.fetch_add(1, Ordering::Relaxed);
+						if i >= marks.len() { return checked; }
+						checked.push((i, self.check_url(marks[i].url())));
+					}
+				}))
+				.collect();
+			workers.into_iter().flat_map(|w| w.join().expect("a link check panicked")).collect()
+		});
+
+		results.sort_by_key(|(i, _)| *i);
+		results.into_iter().map(|(_, r)| r).collect()
+	}
+
+	fn check_url(&self, url: &str) -> Option<sql::LinkCheck> {
+		let mut check: sql::LinkCheck = sql::LinkCheck {
+			status: None,
+			error: None,
+			redirect: None,
+			permanent: false,
+			checked_at: String::new(),
+		};
+
+		if is_http_url(url) {
+			// servers which reject HEAD requests are asked again with GET
+			let followed: Result<Followed, String> = match follow(self.client, Method::Head, url) {
+				Ok(f) if f.response.status >= 400 => { follow(self.client, Method::Get, url) }
+				result => { result }
+			};
+			match followed {
+				Ok(f) => {
+					check.status = Some(f.response.status);
+					check.redirect = f.redirect;
+					check.permanent = f.permanent;
+				}
+				Err(e) => { check.error = Some(e); }
+			}
+		} else if paths::is_local(url) {
+			if !paths::resolve(url, &self.base_directory).exists() { check.error = Some("no such file or directory".to_string()); }
+		} else {
+			return None;
+		}
+		Some(check)
+	}
+}
+
+pub fn is_http_url(url: &str) -> bool {
+	url.split_once("://").is_some_and(|(scheme, _)| scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https"))
+}
+
+/// Final response of a request whose redirects were followed.
+pub struct Followed {
+	pub response: Response,
+	/// url the request was redirected to
+	pub redirect: Option<String>,
+	/// whether every redirect on the way was permanent (301 or 308)
+	pub permanent: bool,
+}
+
+/// Sends a request to `url` and follows up to `MAX_REDIRECTS` redirects.
+pub fn follow(client: &dyn HttpClient, method: Method, url: &str) -> Result<Followed, String> {
+	let mut current: String = url.to_string();
+	let mut redirect: Option<String> = None;
+	let mut permanent: bool = true;
+
+	for _ in 0..MAX_REDIRECTS {
+		let mut response: Response = client.request(method, &current)?;
+		match (response.status, response.location.take()) {
+			(301 | 302 | 303 | 307 | 308, Some(location)) => {
+				permanent &= matches!(response.status, 301 | 308);
+				current = resolve_location(&current, &location);
+				redirect = Some(current.clone());
+			}
+			_ => { return Ok(Followed { response, redirect, permanent }); }
+		}
+	}
+	Err(format!("more than {} redirects", MAX_REDIRECTS))
+}
+
+/// Turns the value of a `Location` header (or any other relative url) into an absolute url.
+pub fn resolve_location(base: &str, location: &str) -> String {
+	if location.contains("://") { return location.to_string(); }
+	let (scheme, rest): (&str, &str) = base.split_once("://").unwrap_or(("http", base));
+	if let Some(network_path) = location.strip_prefix("//") {
+		return format!("{}://{}", scheme, network_path);
+	}
+
+	let rest: &str = rest.split(['?', '#']).next().unwrap_or(rest);
+	let host: &str = rest.split('/').next().unwrap_or(rest);
+	if location.starts_with('/') {
+		return format!("{}://{}{}", scheme, host, location);
+	}
+	match rest.rfind('/') {
+		Some(i) => { format!("{}://{}/{}", scheme, &rest[..i], location) }
+		None => { format!("{}://{}/{}", scheme, host, location) }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::HashMap;
+	use std::io::Write;
+	use std::net::TcpListener;
+	use std::path::Path;
+
+	/// Answers every request from a table of `(method, url) -> (status, location)`, unknown urls get a 404.
+	struct StubClient {
+		responses: HashMap<(&'static str, &'static str), (u16, Option<&'static str>)>,
+	}
+
+	impl StubClient {
+		fn new(responses: &[(&'static str, &'static str, u16, Option<&'static str>)]) -> StubClient {
+			StubClient {
+				responses: responses.iter().map(|(method, url, status, location)| ((*method, *url), (*status, *location))).collect(),
+			}
+		}
+	}
+
+	impl HttpClient for StubClient {
+		fn request(&self, method: Method, url: &str) -> Result<Response, String> {
+			let method: &str = match method {
+				Method::Head => { "HEAD" }
+				Method::Get => { "GET" }
+			};
+			let (status, location) = self.responses.get(&(method, url)).copied().unwrap_or((404, None));
+			Ok(Response { status, location: location.map(|l| l.to_string()), content_type: None, body: vec![] })
+		}
+	}
+
+	fn checker<'a>(client: &'a dyn HttpClient, base_directory: &Path) -> Checker<'a> {
+		let mut config: config::Config = config::Config::default();
+		config.path_base_directory = base_directory.to_path_buf();
+		Checker::new(client, &config)
+	}
+
+	#[test]
+	fn permanent_redirects_are_followed() {
+		let client: StubClient = StubClient::new(&[
+			("HEAD", "http://old.example.com", 301, Some("https://old.example.com/")),
+			("HEAD", "https://old.example.com/", 308, Some("https://new.example.com/")),
+			("HEAD", "https://new.example.com/", 200, None),
+		]);
+		let followed: Followed = follow(&client, Method::Head, "http://old.example.com").unwrap();
+		assert_eq!(followed.response.status, 200);
+		assert_eq!(followed.redirect.as_deref(), Some("https://new.example.com/"));
+		assert!(followed.permanent);
+	}
+
+	#[test]
+	fn a_temporary_redirect_makes_the_chain_temporary() {
+		for temporary in [302, 307] {
+			let client: StubClient = StubClient::new(&[
+				("HEAD", "https://example.com/a", 301, Some("/b")),
+				("HEAD", "https://example.com/b", temporary, Some("c")),
+				("HEAD", "https://example.com/c", 200, None),
+			]);
+			let followed: Followed = follow(&client, Method::Head, "https://example.com/a").unwrap();
+			assert_eq!(followed.redirect.as_deref(), Some("https://example.com/c"));
+			assert!(!followed.permanent, "{} is temporary", temporary);
+		}
+	}
+
+	#[test]
+	fn redirect_loops_are_an_error() {
+		let client: StubClient = StubClient::new(&[
+			("HEAD", "https://example.com/a", 302, Some("/b")),
+			("HEAD", "https://example.com/b", 302, Some("/a")),
+		]);
+		assert!(follow(&client, Method::Head, "https://example.com/a").is_err());
+	}
+
+	#[test]
+	fn rejected_head_requests_are_sent_again_with_get() {
+		let client: StubClient = StubClient::new(&[
+			("HEAD", "https://example.com/no-head", 405, None),
+			("GET", "https://example.com/no-head", 200, None),
+			("HEAD", "https://example.com/forbidden", 403, None),
+			("GET", "https://example.com/forbidden", 301, Some("/moved")),
+			("GET", "https://example.com/moved", 200, None),
+		]);
+		let checker: Checker = checker(&client, Path::new(""));
+
+		let check: sql::LinkCheck = checker.check_url("https://example.com/no-head").unwrap();
+		assert_eq!(check.status, Some(200));
+		assert!(check.error.is_none());
+		let check: sql::LinkCheck = checker.check_url("https://example.com/forbidden").unwrap();
+		assert_eq!(check.status, Some(200));
+		assert_eq!(check.redirect.as_deref(), Some("https://example.com/moved"));
+		assert!(check.permanent);
+		let check: sql::LinkCheck = checker.check_url("https://example.com/gone").unwrap();
+		assert_eq!(check.status, Some(404));
+	}
+
+	#[test]
+	fn unanswered_requests_time_out() {
+		let listener: TcpListener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let url: String = format!("http://{}/", listener.local_addr().unwrap());
+		// accepts the connections but never answers
+		let _server = thread::spawn(move || {
+			let connections: Vec<_> = listener.incoming().take(2).collect();
+			thread::sleep(Duration::from_secs(5));
+			drop(connections);
+		});
+
+		let client: UreqClient = UreqClient::new(Duration::from_millis(200));
+		let check: sql::LinkCheck = checker(&client, Path::new("")).check_url(&url).unwrap();
+		assert!(check.status.is_none());
+		assert!(check.error.is_some());
+	}
+
+	#[test]
+	fn the_ureq_client_does_not_follow_redirects_itself() {
+		let listener: TcpListener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let url: String = format!("http://{}/", listener.local_addr().unwrap());
+		let _server = thread::spawn(move || {
+			let mut connection = listener.incoming().next().unwrap().unwrap();
+			let mut request: [u8; 1024] = [0; 1024];
+			let _ = connection.read(&mut request);
+			let _ = connection.write_all(b"HTTP/1.1 301 Moved Permanently\r\nLocation: /new\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+		});
+
+		let client: UreqClient = UreqClient::new(Duration::from_secs(5));
+		let response: Response = client.request(Method::Head, &url).unwrap();
+		assert_eq!(response.status, 301);
+		assert_eq!(response.location.as_deref(), Some("/new"));
+	}
+
+	#[test]
+	fn local_paths_are_checked_for_existence() {
+		let directory = tempfile::tempdir().unwrap();
+		std::fs::write(directory.path().join("notes.md"), "").unwrap();
+		let client: StubClient = StubClient::new(&[]);
+		let checker: Checker = checker(&client, directory.path());
+
+		let existing: String = directory.path().join("notes.md").to_string_lossy().to_string();
+		assert!(checker.check_url(&existing).unwrap().error.is_none());
+		assert!(checker.check_url(&format!("file://{}", existing)).unwrap().error.is_none());
+		assert!(checker.check_url("./notes.md").unwrap().error.is_none());
+		assert!(checker.check_url("./missing.md").unwrap().error.is_some());
+		assert!(checker.check_url(&directory.path().join("missing.md").to_string_lossy()).unwrap().error.is_some());
+		assert!(checker.check_url("mailto:someone@example.com").is_none());
+	}
+
+	#[test]
+	fn locations_are_resolved_against_the_url() {
+		let base: &str = "https://example.com/docs/page.html?q=1#top";
+		assert_eq!(resolve_location(base, "http://other.example.com/x"), "http://other.example.com/x");
+		assert_eq!(resolve_location(base, "//cdn.example.com/x"), "https://cdn.example.com/x");
+		assert_eq!(resolve_location(base, "/root"), "https://example.com/root");
+		assert_eq!(resolve_location(base, "other.html"), "https://example.com/docs/other.html");
+		assert_eq!(resolve_location("https://example.com", "page"), "https://example.com/page");
+		assert_eq!(resolve_location("https://example.com?q=/x", "page"), "https://example.com/page");
+	}
+}
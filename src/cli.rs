@@ -1,12 +1,34 @@
+use crate::archive;
+use crate::backup;
+use crate::check;
+use crate::clipboard;
+use crate::completions;
 use crate::config;
 use crate::sql;
+use crate::stats;
+use crate::sync;
 use crate::dmenu_handler;
+use crate::editor;
+use crate::manpage;
+use crate::merge;
+use crate::opener;
+use crate::metadata;
+use crate::paths;
+use crate::prompt;
+use crate::tui;
 
-use std::path::PathBuf;
-use clap::{Parser, Subcommand};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::time::Duration;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::{ArgValueCandidates, CompleteEnv};
 use rusqlite::{Connection};
 
+const DEFAULT_PROFILE_NAME: &str = "default";
+
+/// Bookmarks for urls and local paths, searched with dmenu
 #[derive(Parser)]
+#[command(name = "schwimark", version)]
 struct StartArgs{
 	#[command(subcommand)]
 	operation: Operation,
@@ -19,6 +41,14 @@ struct StartArgs{
 	#[arg(short, long="database")]
 	database_path: Option<PathBuf>,
 
+	/// use the database and menu settings of a profile defined in the config
+	#[arg(short, long, add = ArgValueCandidates::new(completions::profiles))]
+	profile: Option<String>,
+
+	/// search the SchWiMarks of all profiles at once (only for open, show and copy)
+	#[arg(short, long, conflicts_with_all = ["profile", "database_path"])]
+	all_profiles: bool,
+
 	/// the command to which the list of bookmarks is piped
 	#[arg(short='m', long)]
 	dmenu_command: Option<String>,
@@ -35,19 +65,223 @@ struct StartArgs{
 #[derive(Subcommand)]
 enum Operation {
 	/// Opens the selection and will delete the entry that was selected
-	Delete,
+	Delete {
+		/// name, unique name prefix, id or uuid of the SchWiMark (skips the selection)
+		#[arg(add = ArgValueCandidates::new(completions::marks))]
+		mark: Option<String>,
+	},
 	/// Opens the selection and will continue to the update cli for the entry that was selected
-	Update,
-	/// Opens the add SchWiMark cli
-	Add,
-	/// Clears the database (WARNING: all data will be lost)
+	Update {
+		/// name, unique name prefix, id or uuid of the SchWiMark (skips the selection)
+		#[arg(add = ArgValueCandidates::new(completions::marks))]
+		mark: Option<String>,
+	},
+	/// Opens the add SchWiMark cli, the name and description are prefilled from the page of an http(s) url
+	Add {
+		/// do not fetch the page to prefill the name, description and tag suggestions
+		#[arg(long)]
+		no_fetch: bool,
+
+		/// use the url/path on the clipboard instead of asking for it
+		#[arg(long)]
+		from_clipboard: bool,
+	},
+	/// Moves all SchWiMarks to the trash
 	Clear,
+	/// Lists, restores or permanently deletes SchWiMarks in the trash
+	Trash {
+		#[command(subcommand)]
+		operation: TrashOperation,
+	},
+	/// Reverts the last add, update, delete, clear or restore
+	Undo,
+	/// Creates, lists or restores backups of the database
+	Backup {
+		#[command(subcommand)]
+		operation: BackupOperation,
+	},
 	/// Opens the selection and will attempt to open the url/path specified
-	Open,
+	Open {
+		/// name, unique name prefix, id or uuid of the SchWiMark (skips the selection)
+		#[arg(conflicts_with = "tag", add = ArgValueCandidates::new(completions::marks))]
+		mark: Option<String>,
+
+		/// only offer SchWiMarks with this tag in the selection
+		#[arg(long, add = ArgValueCandidates::new(completions::tags))]
+		tag: Option<String>,
+
+		/// open all SchWiMarks with the tag instead of selecting one
+		#[arg(long, requires = "tag")]
+		all: bool,
+
+		/// milliseconds to wait between opening SchWiMarks with --all
+		#[arg(long, requires = "all")]
+		delay: Option<u64>,
+
+		/// open the latest snapshot created by `archive` instead of the url
+		#[arg(long, conflicts_with = "all")]
+		archived: bool,
+	},
+	/// Downloads snapshots of the pages of SchWiMarks into the archive next to the database
+	Archive {
+		/// name, unique name prefix, id or uuid of the SchWiMark (skips the selection)
+		#[arg(conflicts_with = "tag", add = ArgValueCandidates::new(completions::marks))]
+		mark: Option<String>,
+
+		/// archive all SchWiMarks with this tag
+		#[arg(long, add = ArgValueCandidates::new(completions::tags))]
+		tag: Option<String>,
+
+		/// also embed images, stylesheets and scripts so the snapshot is a single self-contained file
+		#[arg(long)]
+		assets: bool,
+	},
 	/// Opens the selection and will print out the selection made
-	Show,
+	Show {
+		/// name, unique name prefix, id or uuid of the SchWiMark (skips the selection)
+		#[arg(add = ArgValueCandidates::new(completions::marks))]
+		mark: Option<String>,
+	},
+	/// Opens the selection and will copy the url/path of the entry that was selected to the clipboard
+	Copy {
+		/// name, unique name prefix, id or uuid of the SchWiMark (skips the selection)
+		#[arg(add = ArgValueCandidates::new(completions::marks))]
+		mark: Option<String>,
+	},
+	/// Opens the selection and then a menu of what to do with the entry that was selected
+	Action {
+		/// name, unique name prefix, id or uuid of the SchWiMark (skips the selection)
+		#[arg(add = ArgValueCandidates::new(completions::marks))]
+		mark: Option<String>,
+	},
+	/// Runs a custom action defined in the config on the selected SchWiMarks
+	Run {
+		/// name of the action (`[actions.<name>]` in the config)
+		#[arg(add = ArgValueCandidates::new(completions::actions))]
+		action: String,
+
+		/// names, unique name prefixes, ids or uuids of the SchWiMarks (skips the selection)
+		#[arg(add = ArgValueCandidates::new(completions::marks))]
+		marks: Vec<String>,
+
+		/// run the action on all SchWiMarks with this tag
+		#[arg(long, conflicts_with = "marks", add = ArgValueCandidates::new(completions::tags))]
+		tag: Option<String>,
+	},
 	/// will print out all SchWImarks to the console
 	ShowAll,
+	/// Opens the selected SchWiMarks in $VISUAL/$EDITOR and applies the changes made
+	Edit {
+		/// names, unique name prefixes, ids or uuids of the SchWiMarks (skips the selection)
+		#[arg(add = ArgValueCandidates::new(completions::marks))]
+		marks: Vec<String>,
+
+		/// edit all SchWiMarks with this tag
+		#[arg(long, conflicts_with = "marks", add = ArgValueCandidates::new(completions::tags))]
+		tag: Option<String>,
+	},
+	/// Opens a full-screen manager to browse, filter, edit and delete SchWiMarks
+	Tui,
+	/// Exports the SchWiMarks to the sync git repository, merges them with the remote and imports the result
+	Sync {
+		/// resolve conflicting changes by keeping this side instead of asking
+		#[arg(long, value_parser = ["local", "remote"])]
+		prefer: Option<String>,
+	},
+	/// Imports the SchWiMarks of another database, combining the tags of SchWiMarks which exist in both
+	Merge {
+		/// path to the other SchWiMark database
+		database: PathBuf,
+
+		/// resolve differing fields by keeping this side instead of asking
+		#[arg(long, value_parser = ["local", "remote"])]
+		prefer: Option<String>,
+	},
+	/// Checks whether the urls and paths of the SchWiMarks can still be reached and reports dead links
+	Check {
+		/// only check SchWiMarks with this tag
+		#[arg(long, add = ArgValueCandidates::new(completions::tags))]
+		tag: Option<String>,
+
+		/// tag SchWiMarks with dead links (with "dead" if no tag is given)
+		#[arg(long, num_args = 0..=1, default_missing_value = "dead", value_name = "TAG", add = ArgValueCandidates::new(completions::tags))]
+		tag_dead: Option<String>,
+
+		/// update the url of permanently redirected SchWiMarks without asking
+		#[arg(long)]
+		update_redirects: bool,
+
+		/// will print out the dead links found by the last check instead of checking again
+		#[arg(long, conflicts_with_all = ["tag", "tag_dead", "update_redirects"])]
+		list: bool,
+	},
+	/// will print out statistics about the SchWiMarks (tags, duplicates, most opened, domains, ...)
+	Stats {
+		/// print JSON instead of tables
+		#[arg(long)]
+		json: bool,
+
+		/// number of most/least opened SchWiMarks and domains listed
+		#[arg(long, default_value_t = 10)]
+		top: usize,
+	},
+	/// Checks, prints or creates the config file
+	Config {
+		#[command(subcommand)]
+		operation: ConfigOperation,
+	},
+	/// will print out the script registering the completions with a shell
+	Completions {
+		shell: completions::Shell,
+	},
+	/// will print out the man page
+	Man {
+		/// write a man page for the program and every command into this directory instead
+		#[arg(long)]
+		directory: Option<PathBuf>,
+	},
+}
+
+#[derive(Subcommand)]
+enum ConfigOperation {
+	/// Validates the config and reports all errors with their line and column
+	Check,
+	/// will print out the effective configuration and where each value comes from
+	Show,
+	/// Writes a commented template to the config file
+	Init {
+		/// overwrite an existing config file
+		#[arg(long)]
+		force: bool,
+	},
+}
+
+#[derive(Subcommand)]
+enum TrashOperation {
+	/// will print out all SchWiMarks in the trash
+	List,
+	/// Opens the selection of SchWiMarks in the trash and will restore the one selected
+	Restore {
+		/// name, id or uuid of the SchWiMark in the trash (skips the selection)
+		#[arg(add = ArgValueCandidates::new(completions::trash))]
+		mark: Option<String>,
+	},
+	/// Permanently deletes all SchWiMarks in the trash (WARNING: they cannot be restored)
+	Empty,
+}
+
+#[derive(Subcommand)]
+enum BackupOperation {
+	/// Creates a backup of the database now
+	Create,
+	/// will print out all backups, newest first
+	List,
+	/// Replaces the database with a backup (the current state is backed up first)
+	Restore {
+		/// number shown by `backup list`, file name of the backup or "latest"
+		#[arg(add = ArgValueCandidates::new(completions::backups))]
+		backup: String,
+	},
 }
 
 #[derive(Parser)]
@@ -61,6 +295,8 @@ struct MarkArgs {
 
 
 pub fn start_cli() {
+	// answers the completion requests of the scripts printed by `completions`
+	CompleteEnv::with_factory(StartArgs::command).var(completions::COMPLETE_VAR).complete();
 	let start_args: StartArgs = StartArgs::parse();
 	let mut config: config::Config;
 
@@ -69,85 +305,359 @@ pub fn start_cli() {
 		None => config = config::Config::default(),
 	}
 
-	config.load_config(
+	if let Operation::Config { operation: ConfigOperation::Init { force } } = start_args.operation {
+		match config.write_template(force) {
+			Ok(_) => { println!("wrote \"{}\"", config.config_file().display()); }
+			Err(e) => { println!("{}", e); exit(1) }
+		}
+		return;
+	}
+
+	match start_args.operation {
+		Operation::Completions { shell } => {
+			match completions::script(shell, StartArgs::command().get_name()) {
+				Ok(script) => { print!("{}", script); }
+				Err(e) => { println!("{}", e); exit(1) }
+			}
+			return;
+		}
+		Operation::Man { directory: Some(directory) } => {
+			if let Err(e) = manpage::write_pages(StartArgs::command(), &directory) {
+				println!("{}", e);
+				exit(1)
+			}
+			return;
+		}
+		Operation::Man { directory: None } => {
+			match manpage::render(StartArgs::command()) {
+				Ok(page) => { print!("{}", page); }
+				Err(e) => { println!("{}", e); exit(1) }
+			}
+			return;
+		}
+		_ => {}
+	}
+
+	if let Err(e) = config.load_config(
+		start_args.profile,
 		start_args.database_path,
 		start_args.dmenu_command,
 		start_args.dmenu_mark_arguments,
 		start_args.dmenu_tag_arguments,
-		);
+		) {
+		println!("{}", e);
+		exit(1)
+	}
+
+	if let Operation::Config { operation } = start_args.operation {
+		config_cli(&config, operation);
+		return;
+	}
 
-	let database = sql::create_database(&config.database_file).expect("failed to create/open the database");
+	if start_args.all_profiles {
+		all_profiles_cli(&config, start_args.operation);
+		return;
+	}
+
+	let backups: backup::Backups = backup::Backups::new(&config);
+	let mut database = sql::create_database(&config.database_file, &backups).expect("failed to create/open the database");
+
+	if let Err(e) = backups.create_scheduled(&database) { println!("{}", e); }
 
 	match start_args.operation {
-		Operation::Delete => {
-			let id: i64 = start_mark_selection(&database, &config);
+		Operation::Delete { mark } => {
+			let id: i64 = resolve_mark(&database, &config, mark);
 			if id < 0 { return; }
 			sql::delete_mark(&database, id).expect("failed to delete this mark");
 		}
-		Operation::Update => {
-			update_cli(&database, &config);
+		Operation::Update { mark } => {
+			let id: i64 = resolve_mark(&database, &config, mark);
+			if id < 0 { return; }
+			group_operations(&database, sql::Operation::Update);
+			update_cli(&database, &config, id);
 		}
-		Operation::Add => {
-			add_cli(&database);
+		Operation::Add { no_fetch, from_clipboard } => {
+			add_cli(&database, &config, !no_fetch, from_clipboard);
 		}
 		Operation::Clear => {
-			clear_cli(&database);
+			clear_cli(&database, &backups);
 		}
-		Operation::Open => {
-			let id: i64 = start_mark_selection(&database, &config);
+		Operation::Trash { operation } => {
+			trash_cli(&database, &config, &backups, operation);
+		}
+		Operation::Backup { operation } => {
+			backup_cli(&mut database, &backups, operation);
+		}
+		Operation::Undo => {
+			match sql::undo(&database).expect("failed to undo the last operation") {
+				Some(undone) => {
+					println!("undid {} of {} SchWiMark(s)", undone.operation, undone.count);
+					for (name, new_name) in undone.renamed {
+						println!("restored \"{}\" as \"{}\" as its name is taken", name, new_name);
+					}
+				}
+				None => { println!("nothing to undo"); }
+			}
+		}
+		Operation::Open { mark: _, tag: Some(tag), all: true, delay, archived: _ } => {
+			open_all_cli(&database, &config, &tag, delay);
+		}
+		Operation::Open { mark: _, tag: Some(tag), all: false, delay: _, archived } => {
+			let ids: Vec<i64> = sql::get_marks_by_tag(&database, &tag).expect("failed to query marks");
+			let id: i64 = start_filtered_mark_selection(&database, &config, &ids);
+			if id < 0 { return; }
+			open_cli(&database, &config, id, archived);
+		}
+		Operation::Open { mark, tag: None, all: _, delay: _, archived } => {
+			let id: i64 = resolve_mark(&database, &config, mark);
 			if id < 0 { return; }
-			sql::open_mark(&database, id).expect("failed to open mark")
+			open_cli(&database, &config, id, archived);
 		}
-		Operation::Show => {
-			let id: i64 = start_mark_selection(&database, &config);
+		Operation::Archive { mark, tag, assets } => {
+			archive_cli(&database, &config, mark, tag, assets);
+		}
+		Operation::Show { mark } => {
+			let id: i64 = resolve_mark(&database, &config, mark);
 			if id < 0 { return; }
 			sql::show_mark(&database, id).expect("failed to print mark")
 		}
+		Operation::Copy { mark } => {
+			let id: i64 = resolve_mark(&database, &config, mark);
+			if id < 0 { return; }
+			copy_cli(&database, &config, id);
+		}
+		Operation::Action { mark } => {
+			let id: i64 = resolve_mark(&database, &config, mark);
+			if id < 0 { return; }
+			action_cli(&database, &config, id);
+		}
+		Operation::Run { action, marks, tag } => {
+			run_cli(&database, &config, &action, marks, tag);
+		}
 		Operation::ShowAll => {
 			sql::show_all_marks(&database).unwrap();
 		}
+		Operation::Edit { marks, tag } => {
+			group_operations(&database, sql::Operation::Update);
+			edit_cli(&database, &config, marks, tag);
+		}
+		Operation::Tui => {
+			if let Err(e) = tui::start_tui(&database) {
+				println!("{}", e);
+				exit(1)
+			}
+		}
+		Operation::Sync { prefer } => {
+			group_operations(&database, sql::Operation::Sync);
+			sync_cli(&database, &config, &backups, prefer);
+		}
+		Operation::Merge { database: other, prefer } => {
+			group_operations(&database, sql::Operation::Merge);
+			merge_cli(&database, &backups, &other, prefer);
+		}
+		Operation::Check { tag, tag_dead, update_redirects, list: false } => {
+			group_operations(&database, sql::Operation::Update);
+			check_cli(&database, &config, tag, tag_dead, update_redirects);
+		}
+		Operation::Check { list: true, .. } => {
+			dead_links_cli(&database);
+		}
+		Operation::Stats { json, top } => {
+			stats_cli(&database, json, top);
+		}
+		Operation::Config { .. } => { unreachable!("config operations are handled before opening the database") }
+		Operation::Completions { .. } | Operation::Man { .. } => {
+			unreachable!("completions and the man page are handled before opening the database")
+		}
+	}
+}
+
+/// Resolves the SchWiMark given on the command line by name, unique name prefix, id or uuid.
+/// Opens the selection if no mark was given, or restricted to the candidates if it is ambiguous.
+/// Returns -1 if no mark was found or selected.
+fn resolve_mark(database: &Connection, config: &config::Config, mark: Option<String>) -> i64 {
+	let query: String = match mark {
+		Some(q) => { q }
+		None => { return start_mark_selection(database, config); }
+	};
+
+	let candidates: Vec<i64> = sql::find_marks(database, &query).expect("failed to query marks");
+	match candidates.len() {
+		0 => {
+			println!("no SchWiMark matches \"{}\"", query);
+			-1
+		}
+		1 => { candidates[0] }
+		_ => { start_filtered_mark_selection(database, config, &candidates) }
 	}
 }
 
 fn start_mark_selection(database: &Connection, config: &config::Config) -> i64 {
-	let mut entries: Vec<String> = sql::get_marks_short(&database).expect("failed to query marks");
-	let selected_item: String = match dmenu_handler::open_mark_search(&config, &mut entries) {
+	let entries: Vec<String> = sql::get_marks_short(database, config).expect("failed to query marks");
+	select_mark_entry(config, &entries)
+}
+
+fn start_filtered_mark_selection(database: &Connection, config: &config::Config, ids: &[i64]) -> i64 {
+	let entries: Vec<String> = sql::get_marks_short(database, config)
+		.expect("failed to query marks")
+		.into_iter()
+		.filter(|e| {
+			e.split('\t').next()
+				.and_then(|id| id.parse::<i64>().ok())
+				.is_some_and(|id| ids.contains(&id))
+		})
+		.collect();
+	select_mark_entry(config, &entries)
+}
+
+/// Like `start_mark_selection`, but returns every selected SchWiMark for menus which allow selecting
+/// several entries (e.g. `rofi -dmenu -multi-select`).
+fn start_multi_mark_selection(database: &Connection, config: &config::Config) -> Vec<i64> {
+	let entries: Vec<String> = sql::get_marks_short(database, config).expect("failed to query marks");
+	match dmenu_handler::open_mark_search(config, &entries) {
+		Ok(selected) => {
+			selected.lines()
+				.filter_map(|l| l.split_whitespace().next().and_then(|id| id.parse::<i64>().ok()))
+				.collect()
+		}
+		Err(e) => {
+			println!("{}", e);
+			vec![]
+		}
+	}
+}
+
+fn select_mark_entry(config: &config::Config, entries: &[String]) -> i64 {
+	let selected_item: String = match dmenu_handler::open_mark_search(config, entries) {
 		Ok(s) => { s }
 		Err(e) => { println!("{}", e); return -1; }
 	};
 	if selected_item.is_empty() { return -1; }
-	selected_item.split_whitespace().nth(0).unwrap().parse::<i64>().unwrap()
+	selected_item.split_whitespace().next().unwrap().parse::<i64>().unwrap()
+}
+
+/// Runs open or show on a SchWiMark selected from the top level database and every profile at once.
+/// Entries in the selection are prefixed with the name of the profile they belong to.
+fn all_profiles_cli(config: &config::Config, operation: Operation) {
+	let mark: Option<String> = match &operation {
+		Operation::Open { mark, tag: None, all: false, delay: _, archived: false } => { mark.clone() }
+		Operation::Show { mark } => { mark.clone() }
+		Operation::Copy { mark } => { mark.clone() }
+		_ => {
+			println!("--all-profiles can only be used with open, show and copy");
+			exit(1)
+		}
+	};
+
+	let mut profiles: Vec<(String, config::Config)> = vec![];
+	if !config.profiles.contains_key(DEFAULT_PROFILE_NAME) {
+		profiles.push((DEFAULT_PROFILE_NAME.to_string(), config.clone()));
+	}
+	for name in config.profiles.keys() {
+		profiles.push((name.clone(), config.with_profile(name).expect("profile exists")));
+	}
+
+	let mut databases: Vec<(String, config::Config, Connection)> = vec![];
+	for (name, profile_config) in profiles {
+		if databases.iter().any(|(_, c, _)| c.database_file == profile_config.database_file) { continue; }
+		let backups: backup::Backups = backup::Backups::new(&profile_config);
+		let database: Connection = sql::create_database(&profile_config.database_file, &backups)
+			.unwrap_or_else(|e| panic!("failed to create/open the database of profile \"{}\": {}", name, e));
+		databases.push((name, profile_config, database));
+	}
+
+	let mut entries: Vec<String> = vec![];
+	for (name, profile_config, database) in databases.iter() {
+		let ids: Option<Vec<i64>> = mark.as_ref().map(|m| sql::find_marks(database, m).expect("failed to query marks"));
+		for entry in sql::get_marks_short(database, profile_config).expect("failed to query marks") {
+			let id: Option<i64> = entry.split('\t').next().and_then(|id| id.parse::<i64>().ok());
+			if ids.as_ref().is_some_and(|ids| !id.is_some_and(|id| ids.contains(&id))) { continue; }
+			entries.push(format!("{}\t{}", name, entry));
+		}
+	}
+
+	if entries.is_empty() && mark.is_some() {
+		println!("no SchWiMark matches \"{}\"", mark.unwrap_or_default());
+		return;
+	}
+	// only a query matching a single SchWiMark skips the menu, without one the menu is always shown
+	let selected_item: String = match (entries.len(), mark.is_some()) {
+		(1, true) => { entries.remove(0) }
+		_ => {
+			match dmenu_handler::open_mark_search(config, &entries) {
+				Ok(s) => { s }
+				Err(e) => { println!("{}", e); return; }
+			}
+		}
+	};
+	let mut fields = selected_item.split('\t');
+	let (profile_name, id): (&str, i64) = match (fields.next(), fields.next().and_then(|id| id.trim().parse::<i64>().ok())) {
+		(Some(p), Some(id)) => { (p, id) }
+		_ => { return; }
+	};
+	let (_, profile_config, database) = match databases.iter().find(|(name, _, _)| name == profile_name) {
+		Some(d) => { d }
+		None => { return; }
+	};
+
+	match operation {
+		Operation::Open { .. } => { sql::open_mark(database, profile_config, id).expect("failed to open mark") }
+		Operation::Copy { .. } => { copy_cli(database, profile_config, id) }
+		_ => { sql::show_mark(database, id).expect("failed to print mark") }
+	}
 }
 
-fn database_entry_cli() -> MarkArgs {
-	let mut input_vec: Vec<String> = vec![];
+fn database_entry_cli(prompt: &mut prompt::Prompt, config: &config::Config, fetch: bool, mut clipboard_url: Option<String>) -> MarkArgs {
+	let mut input_vec: Vec<String> = Vec::with_capacity(6);
+
+	let url: String = loop {
+		let url: String = match clipboard_url.take() {
+			Some(u) => {
+				println!("url/path: {}", u);
+				u
+			}
+			None => { url_cli(prompt, "") }
+		};
+		if path_exists_cli(config, &url) { break url; }
+	};
+	let page: metadata::PageMetadata = match fetch && check::is_http_url(&url) {
+		true => {
+			let client: check::UreqClient = check::UreqClient::new(Duration::from_secs(config.fetch_timeout_secs));
+			metadata::fetch_metadata(&client, &url).unwrap_or_else(|e| {
+				println!("could not fetch the page: {}", e);
+				metadata::PageMetadata::default()
+			})
+		}
+		false => { metadata::PageMetadata::default() }
+	};
 
-	input_vec.reserve(6);
 	input_vec.push("".to_string());
-	input_vec.push(name_cli());
-	input_vec.push(description_cli());
-	input_vec.push(url_cli());
-	input_vec.push(application_cli());
-	input_vec.append(&mut tags_cli());
+	input_vec.push(name_cli(prompt, page.title.as_deref().unwrap_or_default()));
+	input_vec.push(description_cli(prompt, page.description.as_deref().unwrap_or_default()));
+	input_vec.push(url);
+	input_vec.push(application_cli(prompt, ""));
+	if !page.keywords.is_empty() {
+		println!("Suggested tags (complete with tab): {}", page.keywords.join(", "));
+		prompt.suggest_tags(&page.keywords);
+	}
+	input_vec.append(&mut tags_cli(prompt, &[]));
 
 	MarkArgs::try_parse_from(input_vec.iter()).unwrap()
 }
 
 fn clean_string(s: &mut String) -> String {
-	s.retain(|c| !r#"\t\n\0"#.contains(c));
+	s.retain(|c| !sql::FORBIDDEN_CHARACTERS.contains(c));
 	s.to_string()
 }
 
-fn name_cli() -> String {
-	let mut name_buf: String = String::new();
-
+fn name_cli(prompt: &mut prompt::Prompt, current: &str) -> String {
 	loop {
-		name_buf.clear();
-		println!("Enter the name of the new SchWiMark");
-		eprint!("name> ");
-		std::io::stdin().read_line(&mut name_buf).expect("Could not parse name input");
+		println!("Enter the name of the SchWiMark");
+		let mut name_buf: String = match prompt.read_line("name> ", current, prompt::Completion::None) {
+			Some(s) => { s }
+			None => { exit(1) }
+		};
 		name_buf = clean_string(&mut name_buf);
-		if name_buf.ends_with('\n') { name_buf.pop(); }
 		if name_buf.is_empty() {
 			println!("name cannot be empty");
 			continue;
@@ -155,27 +665,22 @@ fn name_cli() -> String {
 	}
 }
 
-fn description_cli() -> String {
-	let mut description_buf: String = String::new();
-
-	println!("Enter the description of the new SchWiMark");
-	eprint!("description> ");
-	std::io::stdin().read_line(&mut description_buf).expect("Could not parse description input");
-	description_buf = clean_string(&mut description_buf);
-	if description_buf.ends_with('\n') { description_buf.pop(); };
-	description_buf
+fn description_cli(prompt: &mut prompt::Prompt, current: &str) -> String {
+	println!("Enter the description of the SchWiMark");
+	let mut description_buf: String = prompt
+		.read_line("description> ", current, prompt::Completion::None)
+		.unwrap_or_default();
+	clean_string(&mut description_buf)
 }
 
-fn url_cli() -> String {
-	let mut url_buf: String = String::new();
-
+fn url_cli(prompt: &mut prompt::Prompt, current: &str) -> String {
 	loop {
-		url_buf.clear();
-		println!("Enter the url or path of the new SchWiMark");
-		eprint!("url/path> ");
-		std::io::stdin().read_line(&mut url_buf).expect("Could not parse description input");
+		println!("Enter the url or path of the SchWiMark");
+		let mut url_buf: String = match prompt.read_line("url/path> ", current, prompt::Completion::None) {
+			Some(s) => { s }
+			None => { exit(1) }
+		};
 		url_buf = clean_string(&mut url_buf);
-		if url_buf.ends_with('\n') { url_buf.pop(); };
 		if url_buf.is_empty() {
 			println!("url cannot be empty");
 			continue;
@@ -184,53 +689,49 @@ fn url_cli() -> String {
 	}
 }
 
-fn application_cli() -> String {
-	let mut application_buf: String = String::new();
+/// Returns false if `url` is a local path which does not exist and the user does not want to keep it anyway.
+fn path_exists_cli(config: &config::Config, url: &str) -> bool {
+	if !paths::is_local(url) { return true; }
+	let path: PathBuf = paths::resolve(url, &config.path_base_directory);
+	path.exists() || confirm_cli(&format!("\"{}\" does not exist, use it anyway? [y/N]: ", path.display()))
+}
 
+fn application_cli(prompt: &mut prompt::Prompt, current: &str) -> String {
 	println!("Enter the default application you want the SchwiMark to be opened with (leave empty to use default application):");
-	eprint!("application> ");
-	std::io::stdin().read_line(&mut application_buf).expect("Could not parse description input");
-	application_buf = clean_string(&mut application_buf);
-	if application_buf.ends_with('\n') { application_buf.pop(); }
-	application_buf
+	println!("Hint: the placeholders {{url}}, {{path}}, {{name}} and {{dirname}} may be used e.g. \"kitty --directory {{path}}\"");
+	let mut application_buf: String = prompt
+		.read_line("application> ", current, prompt::Completion::Applications)
+		.unwrap_or_default();
+	clean_string(&mut application_buf)
 }
 
-fn tags_cli() -> Vec<String> {
+fn tags_cli(prompt: &mut prompt::Prompt, existing: &[String]) -> Vec<String> {
 	let mut tag_vec: Vec<String> = vec![];
 
 	loop {
-		let mut tag_buf: String = String::new();
-		println!("Enter a tag of the new SchWiMark enter nothing to continue");
-		eprint!("tag> ");
-		std::io::stdin().read_line(&mut tag_buf).expect("Could not parse the tag input");
+		println!("Enter a tag of the SchWiMark enter nothing to continue");
+		let mut tag_buf: String = prompt
+			.read_line("tag> ", "", prompt::Completion::Tags)
+			.unwrap_or_default();
 		tag_buf = clean_string(&mut tag_buf);
-		if tag_buf.ends_with('\n') { tag_buf.pop(); }
 
 		if tag_buf.is_empty() {
 			break;
 		}
-		else if tag_vec.contains(&tag_buf) { 
+		else if tag_vec.contains(&tag_buf) || existing.contains(&tag_buf) {
 			println!("tag is already selected for this SchWiMark");
 			continue;
 		}
 		else { tag_vec.push(tag_buf); }
 	}
-	return tag_vec
+	tag_vec
 }
 
-fn update_cli(database: &Connection, config: &config::Config) {
+fn update_cli(database: &Connection, config: &config::Config, update_id: i64) {
+	sql::show_mark(database, update_id).expect("failed to print mark");
+	let mark: sql::SchWiMark = sql::get_mark(database, update_id).expect("failed to query mark");
+	let mut prompt: prompt::Prompt = prompt::Prompt::new(database);
 
-	let mut entries: Vec<String> = sql::get_marks_short(&database).expect("failed to query marks");
-	let selected_item: String = match dmenu_handler::open_mark_search(&config, &mut entries) {
-		Ok(s) => { s }
-		Err(e) => { println!("{}", e); return; }
-	};
-	if selected_item.is_empty() { return; }
-	let update_id: i64 = selected_item.split_once("\t").unwrap().0.parse::<i64>().unwrap();
-
-	sql::show_mark(&database, update_id).expect("failed to print mark");
-	
-	let mut menu_buf: String = String::with_capacity(5);
 	println!("What do you wish to change? (please enter the corresponding letters)\n\
 		n) name\n\
 		d) description\n\
@@ -240,52 +741,55 @@ fn update_cli(database: &Connection, config: &config::Config) {
 		-) remove a tag\n\
 		Hint: if you want to update multiple field you can type both e.g. \"nu\" will enter both the name update menu and the url/path update menu"
 		);
-	eprint!("field> ");
-
-	std::io::stdin().read_line(&mut menu_buf).expect("Could not parse clear confirmation");
+	let menu_buf: String = prompt.read_line("field> ", "", prompt::Completion::None).unwrap_or_default();
 
 	for c in menu_buf.chars() {
 		match c {
 			'n' => { 
-				match sql::update_name(database, update_id, name_cli()) {
+				match sql::update_name(database, update_id, name_cli(&mut prompt, mark.name())) {
 					Ok(_) => {}
 					Err(e) => { panic!("sql failed with error \"{}\"", e) }
 				}
 			}
 			'd' => {
-				match sql::update_description(database, update_id, description_cli()) {
+				match sql::update_description(database, update_id, description_cli(&mut prompt, mark.description())) {
 					Ok(_) => {}
 					Err(e) => { panic!("sql failed with error: \"{}\"", e); }
 				}
 			}
 			'u' => {
-				match sql::update_url(database, update_id, url_cli()) {
+				let url: String = loop {
+					let url: String = url_cli(&mut prompt, mark.url());
+					if path_exists_cli(config, &url) { break url; }
+				};
+				match sql::update_url(database, update_id, url) {
 					Ok(_) => {}
 					Err(e) => { panic!("sql failed with error: \"{}\"", e); }
 				}
 			}
 			'a' => {
-				match sql::update_application(database, update_id, application_cli()) {
+				match sql::update_application(database, update_id, application_cli(&mut prompt, mark.application())) {
 					Ok(_) => {}
 					Err(e) => { panic!("sql failed with error: \"{}\"", e); }
 				}
 			}
 			'+' => {
-				match sql::add_tags(database, update_id, tags_cli()) {
+				let existing_tags: Vec<String> = sql::get_tags(database, update_id).expect("failed to query tags");
+				match sql::add_tags(database, update_id, tags_cli(&mut prompt, &existing_tags)) {
 					Ok(_) => {}
 					Err(e) => { panic!("sql failed with error: \"{}\"", e); }
 				}
 			}
 			'-' => {
 				loop {
-					let mut tag_entries: Vec<String> = sql::get_tags(&database, update_id).expect("failed to query tags");
-					let mut selected_tag: String = match dmenu_handler::open_tag_search(&config, &mut tag_entries) {
+					let tag_entries: Vec<String> = sql::get_tags(database, update_id).expect("failed to query tags");
+					let mut selected_tag: String = match dmenu_handler::open_tag_search(config, &tag_entries) {
 						Ok(r) => { r }
 						Err(_) => { break; }
 					};
 					if selected_tag.ends_with('\n') { selected_tag.pop(); };
 					if selected_tag.is_empty() { break; }
-					sql::delete_tag(&database, update_id, selected_tag).expect("failed to delete the tag from the database");
+					sql::delete_tag(database, update_id, selected_tag).expect("failed to delete the tag from the database");
 				} 
 			}
 			_ => { continue; }
@@ -294,33 +798,461 @@ fn update_cli(database: &Connection, config: &config::Config) {
 
 }
 
-fn add_cli(database: &Connection) {
-	let mark_entry = database_entry_cli();
-	let (_schwimark, _tags): (sql::SchWiMark, sql::Tag) = sql::add_mark(database, mark_entry.name, mark_entry.description, mark_entry.url, mark_entry.application, mark_entry.tags).unwrap();
+fn add_cli(database: &Connection, config: &config::Config, fetch: bool, from_clipboard: bool) {
+	let clipboard_url: Option<String> = match from_clipboard {
+		true => {
+			let clipboard: String = clipboard::paste(config).unwrap_or_else(|e| { println!("{}", e); exit(1) });
+			let mut url: String = clipboard.lines().next().unwrap_or_default().trim().to_string();
+			url = clean_string(&mut url);
+			if url.is_empty() {
+				println!("the clipboard is empty");
+				exit(1)
+			}
+			Some(url)
+		}
+		false => { None }
+	};
+	let mut prompt: prompt::Prompt = prompt::Prompt::new(database);
+	let mark_entry = database_entry_cli(&mut prompt, config, fetch, clipboard_url);
+	let (schwimark, tags): (sql::SchWiMark, sql::Tag) = sql::add_mark(database, mark_entry.name, mark_entry.description, mark_entry.url, mark_entry.application, mark_entry.tags).unwrap();
+	println!("added SchWiMark with id {}\n{}\n{}", tags.markid(), schwimark, tags);
+}
+
+fn edit_cli(database: &Connection, config: &config::Config, marks: Vec<String>, tag: Option<String>) {
+	let ids: Vec<i64> = match (tag, marks.is_empty()) {
+		(Some(tag), _) => { sql::get_marks_by_tag(database, &tag).expect("failed to query marks") }
+		(None, true) => { vec![start_mark_selection(database, config)] }
+		(None, false) => { marks.into_iter().map(|m| resolve_mark(database, config, Some(m))).collect() }
+	};
+	if ids.is_empty() || ids.iter().any(|id| *id < 0) { return; }
+
+	match editor::edit_marks(database, &ids) {
+		Ok(changed) => { println!("updated {} SchWiMark(s)", changed); }
+		Err(e) => {
+			println!("{}", e);
+			exit(1)
+		}
+	}
+}
+
+/// Asks which action to run on a SchWiMark with the menu: the entries of `action_menu` followed by the
+/// custom actions which are not listed there.
+fn action_cli(database: &Connection, config: &config::Config, id: i64) {
+	let mut entries: Vec<String> = config.action_menu.clone();
+	for name in config.actions.keys() {
+		if !entries.contains(name) { entries.push(name.clone()); }
+	}
+	let action: String = match dmenu_handler::open_action_search(config, &entries) {
+		Ok(a) => { a.trim().to_string() }
+		Err(e) => { println!("{}", e); exit(1) }
+	};
+	if action.is_empty() { return; }
+
+	match action.as_str() {
+		"open" => { sql::open_mark(database, config, id).expect("failed to open mark") }
+		"open-with" => {
+			let applications: Vec<String> = sql::get_all_applications(database).expect("failed to query applications");
+			let application: String = match dmenu_handler::open_action_search(config, &applications) {
+				Ok(a) => { a.trim().to_string() }
+				Err(e) => { println!("{}", e); exit(1) }
+			};
+			if application.is_empty() { return; }
+			sql::open_mark_with(database, config, id, &application).expect("failed to open mark")
+		}
+		"copy" => { copy_cli(database, config, id) }
+		"show" => { sql::show_mark(database, id).expect("failed to print mark") }
+		"edit" => {
+			group_operations(database, sql::Operation::Update);
+			if let Err(e) = editor::edit_marks(database, &[id]) {
+				println!("{}", e);
+				exit(1)
+			}
+		}
+		"add-tag" => {
+			let tags: Vec<String> = sql::get_all_tags(database).expect("failed to query tags");
+			let tag: String = match dmenu_handler::open_tag_input(config, &tags) {
+				Ok(t) => { t.trim().to_string() }
+				Err(e) => { println!("{}", e); exit(1) }
+			};
+			if tag.is_empty() || sql::get_tags(database, id).expect("failed to query tags").contains(&tag) { return; }
+			sql::add_tags(database, id, vec![tag]).expect("failed to add the tag");
+		}
+		"delete" => { sql::delete_mark(database, id).expect("failed to delete this mark") }
+		name => {
+			let custom: &config::Action = match config.actions.get(name) {
+				Some(a) => { a }
+				None => { println!("unknown action \"{}\"", name); exit(1) }
+			};
+			run_action_cli(database, config, id, custom);
+		}
+	}
+}
+
+fn run_cli(database: &Connection, config: &config::Config, name: &str, marks: Vec<String>, tag: Option<String>) {
+	let action: &config::Action = match config.actions.get(name) {
+		Some(a) => { a }
+		None => {
+			println!(
+				"unknown action \"{}\" (defined actions: {})",
+				name,
+				config.actions.keys().cloned().collect::<Vec<String>>().join(", "),
+			);
+			exit(1)
+		}
+	};
+	let ids: Vec<i64> = match (tag, marks.is_empty()) {
+		(Some(tag), _) => { sql::get_marks_by_tag(database, &tag).expect("failed to query marks") }
+		(None, true) => { start_multi_mark_selection(database, config) }
+		(None, false) => { marks.into_iter().map(|m| resolve_mark(database, config, Some(m))).collect() }
+	};
+	if ids.iter().any(|id| *id < 0) { return; }
+
+	for id in ids {
+		run_action_cli(database, config, id, action);
+	}
+}
+
+/// Runs a custom action in the foreground, e.g. to print something to the terminal.
+fn run_action_cli(database: &Connection, config: &config::Config, id: i64, action: &config::Action) {
+	let mark: sql::SchWiMark = sql::get_mark(database, id).expect("failed to query mark");
+	let location: String = sql::location(&mark, config);
+	let command: Vec<String> = match action.shell {
+		true => { sql::expand_shell_command(&action.command, &mark, &location).map(|c| vec!["sh".to_string(), "-c".to_string(), c]) }
+		false => { sql::expand_command(&action.command, &mark, &location) }
+	}.unwrap_or_else(|e| { println!("{}", e); exit(1) });
+
+	match std::process::Command::new(&command[0]).args(&command[1..]).status() {
+		Ok(status) if status.success() => {}
+		Ok(status) => { println!("\"{}\" failed with {}", action.command, status); exit(1) }
+		Err(e) => { println!("Executing \"{}\" failed with error: \"{}\"", command[0], e); exit(1) }
+	}
+}
+
+fn copy_cli(database: &Connection, config: &config::Config, id: i64) {
+	let mark: sql::SchWiMark = sql::get_mark(database, id).expect("failed to query mark");
+	let location: String = sql::location(&mark, config);
+	match clipboard::copy(config, &location) {
+		Ok(_) => { println!("copied {}", location); }
+		Err(e) => {
+			println!("failed to copy the url/path of SchWiMark {}: {}", id, e);
+			exit(1)
+		}
+	}
+}
+
+fn open_cli(database: &Connection, config: &config::Config, id: i64, archived: bool) {
+	if !archived {
+		sql::open_mark(database, config, id).expect("failed to open mark");
+		return;
+	}
+
+	let hash: String = match sql::get_latest_snapshot(database, id).expect("failed to query snapshots") {
+		Some(h) => { h }
+		None => {
+			println!("SchWiMark {} has not been archived yet", id);
+			exit(1)
+		}
+	};
+	let opened = archive::Archive::new(config)
+		.extract(&hash)
+		.and_then(|path| opener::Opener::new(config).open(&path.to_string_lossy(), None));
+	if let Err(e) = opened {
+		println!("failed to open the snapshot of SchWiMark {}: {}", id, e);
+		exit(1)
+	}
+}
+
+fn archive_cli(database: &Connection, config: &config::Config, mark: Option<String>, tag: Option<String>, assets: bool) {
+	let ids: Vec<i64> = match tag {
+		Some(tag) => { sql::get_marks_by_tag(database, &tag).expect("failed to query marks") }
+		None => { vec![resolve_mark(database, config, mark)] }
+	};
+	if ids.iter().any(|id| *id < 0) { return; }
+
+	let archive: archive::Archive = archive::Archive::new(config);
+	let client: check::UreqClient = check::UreqClient::new(Duration::from_secs(config.fetch_timeout_secs));
+	let mut failed: usize = 0;
+	for id in ids.iter() {
+		let mark: sql::SchWiMark = sql::get_mark(database, *id).expect("failed to query mark");
+		if !check::is_http_url(mark.url()) {
+			println!("skipped \"{}\": only http(s) urls can be archived", mark.name());
+			continue;
+		}
+		match archive.snapshot(&client, mark.url(), assets) {
+			Ok(hash) => {
+				let added: bool = sql::add_snapshot(database, *id, &hash, mark.url()).expect("failed to record the snapshot");
+				println!("{} \"{}\"", if added { "archived" } else { "unchanged since the last snapshot:" }, mark.name());
+			}
+			Err(e) => {
+				println!("failed to archive \"{}\": {}", mark.name(), e);
+				failed += 1;
+			}
+		}
+	}
+	if failed > 0 { exit(1) }
+}
+
+fn open_all_cli(database: &Connection, config: &config::Config, tag: &str, delay: Option<u64>) {
+	let ids: Vec<i64> = sql::get_marks_by_tag(database, tag).expect("failed to query marks");
+	if ids.is_empty() {
+		println!("no SchWiMark is tagged with \"{}\"", tag);
+		return;
+	}
+
+	if ids.len() > config.open_all_confirm_count
+		&& !confirm_cli(&format!("Are you sure you want to open {} SchWiMarks tagged \"{}\"? [y/N]: ", ids.len(), tag)) {
+		return;
+	}
+
+	let delay: Duration = Duration::from_millis(delay.unwrap_or(config.open_all_delay_ms));
+	sql::open_marks(database, config, &ids, delay).expect("failed to open marks");
+}
+
+fn trash_cli(database: &Connection, config: &config::Config, backups: &backup::Backups, operation: TrashOperation) {
+	let trash: Vec<(i64, String)> = sql::get_trash(database).expect("failed to query the trash");
+
+	match operation {
+		TrashOperation::List => {
+			println!("{0: <3} | {1: <0}", "id", "name");
+			for (id, name) in trash.iter() {
+				println!("{0: <3} | {1: <0}", id, name);
+			}
+		}
+		TrashOperation::Restore { mark } => {
+			let id: i64 = match mark {
+				Some(m) => {
+					let by_uuid: Option<i64> = sql::find_mark_by_uuid(database, &m)
+						.expect("failed to query marks")
+						.filter(|(_, trashed)| *trashed)
+						.map(|(id, _)| id);
+					match trash.iter().find(|(id, name)| *name == m || id.to_string() == m).map(|(id, _)| *id).or(by_uuid) {
+						Some(id) => { id }
+						None => { println!("no SchWiMark in the trash matches \"{}\"", m); return; }
+					}
+				}
+				None => {
+					let entries: Vec<String> = trash.iter().map(|(id, name)| format!("{}\t{}", id, name)).collect();
+					select_mark_entry(config, &entries)
+				}
+			};
+			if id < 0 { return; }
+			match sql::restore_mark(database, id) {
+				Ok(_) => {}
+				Err(e) => { println!("failed to restore SchWiMark {}: {}", id, e); exit(1) }
+			}
+		}
+		TrashOperation::Empty => {
+			if trash.is_empty() { return; }
+			if confirm_cli(&format!("Are you sure you want to permanently delete {} SchWiMarks? [y/N]: ", trash.len())) {
+				backup_before(database, backups, "empty-trash");
+				sql::empty_trash(database).expect("failed to empty the trash");
+			}
+		}
+	}
+}
+
+fn backup_cli(database: &mut Connection, backups: &backup::Backups, operation: BackupOperation) {
+	match operation {
+		BackupOperation::Create => {
+			match backups.create(database, "manual") {
+				Ok(Some(path)) => { println!("created backup \"{}\"", path.display()); }
+				Ok(None) => { println!("backups are disabled (backup_retention = 0)"); }
+				Err(e) => { println!("{}", e); exit(1) }
+			}
+		}
+		BackupOperation::List => {
+			let list: Vec<backup::BackupFile> = backups.list().unwrap_or_else(|e| { println!("{}", e); exit(1) });
+			println!("{0: <3} | {1: <50} | {2: <0}", "nr", "backup", "size");
+			for (i, b) in list.iter().enumerate() {
+				println!("{0: <3} | {1: <50} | {2: <0}", i + 1, b.name, b.size);
+			}
+		}
+		BackupOperation::Restore { backup } => {
+			let backup_file: backup::BackupFile = backups.find(&backup).unwrap_or_else(|e| { println!("{}", e); exit(1) });
+			if !confirm_cli(&format!("Are you sure you want to replace the database with \"{}\"? [y/N]: ", backup_file.name)) { return; }
+			match backups.restore(database, &backup_file) {
+				Ok(_) => { println!("restored \"{}\"", backup_file.name); }
+				Err(e) => { println!("{}", e); exit(1) }
+			}
+		}
+	}
+}
+
+/// Makes every change of the command a single entry of the operation log, so `undo` reverts the whole command.
+fn group_operations(database: &Connection, operation: sql::Operation) {
+	if let Err(e) = sql::group_operations(database, operation) {
+		println!("failed to start the operation log entry: {}", e);
+		exit(1)
+	}
+}
+
+/// Creates a backup before a destructive operation, aborting the operation if that fails.
+fn backup_before(database: &Connection, backups: &backup::Backups, reason: &str) {
+	if let Err(e) = backups.create(database, reason) {
+		println!("{}\naborting as no backup could be created", e);
+		exit(1)
+	}
+}
+
+fn clear_cli(database: &Connection, backups: &backup::Backups) {
+	if confirm_cli("Are you sure you want to move all your bookmarks to the trash? [y/N]: ") {
+		backup_before(database, backups, "clear");
+		sql::clear_database(database).expect("failed to clear database");
+	}
+}
+
+fn resolution(prefer: Option<String>) -> sync::Resolution {
+	match prefer.as_deref() {
+		Some("local") => { sync::Resolution::Local }
+		Some("remote") => { sync::Resolution::Remote }
+		_ => { sync::Resolution::Ask }
+	}
+}
+
+fn sync_cli(database: &Connection, config: &config::Config, backups: &backup::Backups, prefer: Option<String>) {
+	match sync::Sync::new(config).run(database, backups, resolution(prefer)) {
+		Ok(report) => {
+			if report.conflicts > 0 { println!("resolved {} conflict(s)", report.conflicts); }
+			if report.imported > 0 { println!("imported changes to {} SchWiMark(s)", report.imported); }
+			println!(
+				"{}{}{}",
+				if report.committed { "committed local changes, " } else { "no local changes, " },
+				if report.merged { "merged remote changes, " } else { "no remote changes, " },
+				if report.pushed { "pushed" } else { "nothing to push" },
+			);
+		}
+		Err(e) => {
+			println!("{}", e);
+			exit(1)
+		}
+	}
+}
+
+fn merge_cli(database: &Connection, backups: &backup::Backups, other: &Path, prefer: Option<String>) {
+	backup_before(database, backups, "pre-merge");
+	match merge::merge_database(database, other, resolution(prefer)) {
+		Ok(report) => {
+			if report.conflicts > 0 { println!("resolved {} conflict(s)", report.conflicts); }
+			println!("added {} and updated {} SchWiMark(s)", report.added, report.updated);
+		}
+		Err(e) => {
+			println!("{}", e);
+			exit(1)
+		}
+	}
+}
+
+fn check_cli(database: &Connection, config: &config::Config, tag: Option<String>, tag_dead: Option<String>, update_redirects: bool) {
+	let mut marks: Vec<sql::SchWiMark> = sql::get_all_marks(database)
+		.expect("failed to query marks")
+		.into_iter()
+		.map(|(m, _)| m)
+		.collect();
+	if let Some(tag) = tag {
+		let ids: Vec<i64> = sql::get_marks_by_tag(database, &tag).expect("failed to query marks");
+		marks.retain(|m| ids.contains(&m.id()));
+	}
+
+	let client: check::UreqClient = check::UreqClient::new(Duration::from_secs(config.check_timeout_secs));
+	let results: Vec<Option<sql::LinkCheck>> = check::Checker::new(&client, config).check(&marks);
+
+	let (mut checked, mut redirected, mut dead): (usize, usize, usize) = (0, 0, 0);
+	for (mark, result) in marks.iter().zip(results) {
+		let mut result: sql::LinkCheck = match result {
+			Some(r) => { r }
+			None => { continue; }
+		};
+		checked += 1;
+
+		if result.is_dead() {
+			dead += 1;
+			println!("dead: {} ({}): {}", mark.name(), mark.url(), result);
+			if let Some(tag) = &tag_dead {
+				let tags: Vec<String> = sql::get_tags(database, mark.id()).expect("failed to query tags");
+				if !tags.contains(tag) {
+					sql::add_tags(database, mark.id(), vec![tag.clone()]).expect("failed to tag the SchWiMark");
+				}
+			}
+		} else if result.is_permanent_redirect() {
+			redirected += 1;
+			let target: String = result.redirect.clone().unwrap_or_default();
+			println!("moved: {} ({}) -> {}", mark.name(), mark.url(), target);
+			if update_redirects || confirm_cli(&format!("Update the url of \"{}\" to {}? [y/N]: ", mark.name(), target)) {
+				sql::update_url(database, mark.id(), target).expect("failed to update the url");
+				result.redirect = None;
+				result.permanent = false;
+			}
+		}
+		sql::record_link_check(database, mark.id(), &result).expect("failed to record the link check");
+	}
+
+	println!("checked {} SchWiMark(s): {} dead, {} moved permanently", checked, dead, redirected);
+}
+
+fn dead_links_cli(database: &Connection) {
+	let checks: Vec<(sql::SchWiMark, sql::LinkCheck)> = sql::get_link_checks(database).expect("failed to query link checks");
+	println!("{0: <3} | {1: <30} | {2: <50} | {3: <19} | {4: <0}", "id", "name", "url/path", "checked", "result");
+	for (mark, check) in checks.iter().filter(|(_, c)| c.is_dead()) {
+		println!("{0: <3} | {1: <30} | {2: <50} | {3: <19} | {4: <0}", mark.id(), mark.name(), mark.url(), check.checked_at, check);
+	}
+}
+
+fn stats_cli(database: &Connection, json: bool, top: usize) {
+	let statistics: sql::Stats = sql::get_stats(database, top).expect("failed to query statistics");
+	match json {
+		true => {
+			match stats::json(&statistics) {
+				Ok(j) => { print!("{}", j); }
+				Err(e) => { println!("failed to render the statistics: {}", e); exit(1) }
+			}
+		}
+		false => { print!("{}", stats::table(&statistics)); }
+	}
+}
+
+/// Runs the config operations which need the loaded config, errors were already reported while loading it.
+fn config_cli(config: &config::Config, operation: ConfigOperation) {
+	match operation {
+		ConfigOperation::Check => {
+			println!("\"{}\" is valid", config.config_file().display());
+		}
+		ConfigOperation::Show => {
+			println!("# config file: {}", config.config_file().display());
+			for file in config.loaded_files() { println!("# read: {}", file.display()); }
+			if let Some(profile) = &config.profile { println!("# profile: {}", profile); }
+			for (key, value, source) in config.settings() {
+				println!("{} = {} # {}", key, value, source);
+			}
+			if !config.profiles.is_empty() {
+				println!("# defined profiles: {}", config.profiles.keys().cloned().collect::<Vec<String>>().join(", "));
+			}
+		}
+		ConfigOperation::Init { .. } => { unreachable!("config init is handled before loading the config") }
+	}
 }
 
-fn clear_cli(database: &Connection) {
+fn confirm_cli(prompt: &str) -> bool {
 	let mut input_buf: String = String::with_capacity(2);
 
 	loop {
 		input_buf.clear();
-		eprint!("Are you sure you want to clear all your bookmarks? [y/N]: ");
-		std::io::stdin().read_line(&mut input_buf).expect("Could not parse clear confirmation");
+		eprint!("{}", prompt);
+		std::io::stdin().read_line(&mut input_buf).expect("Could not parse confirmation");
 		let line = input_buf.trim();
 		let args = shlex::split(line).ok_or("error: Invalid quoting").unwrap();
 
+		if args.is_empty() { return false; }
 		if args.len() > 1 { continue; }
-		if args[0].chars().nth(1) != None { continue; }
-		let conf_char: char = match args[0].chars().nth(0) {
+		if args[0].chars().nth(1).is_some() { continue; }
+		let conf_char: char = match args[0].chars().next() {
 			Some(c) => { c }
 			None => { continue; }
 		};
 
 		if conf_char == 'y' || conf_char == 'Y' {
-			sql::clear_database(database).expect("failed to clear database");
-			break;
+			return true;
 		} else if conf_char == 'n' || conf_char == 'N' {
-			break;
+			return false;
 		} else {
 			continue;
 		}
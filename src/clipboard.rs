@@ -0,0 +1,58 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use crate::config;
+
+const WAYLAND_PASTE_COMMAND: &str = "wl-paste --no-newline";
+const WAYLAND_COPY_COMMAND: &str = "wl-copy";
+const X11_PASTE_COMMAND: &str = "xclip -selection clipboard -out";
+const X11_COPY_COMMAND: &str = "xclip -selection clipboard -in";
+
+/// Returns the text on the clipboard without surrounding whitespace.
+pub fn paste(config: &config::Config) -> Result<String, String> {
+	let command: Vec<String> = clipboard_command(&config.clipboard_paste_command, WAYLAND_PASTE_COMMAND, X11_PASTE_COMMAND)?;
+	let output = Command::new(&command[0])
+		.args(&command[1..])
+		.stdin(Stdio::null())
+		.stderr(Stdio::inherit())
+		.output()
+		.map_err(|e| format!("executing \"{}\" failed with error: \"{}\"", command[0], e))?;
+	if !output.status.success() {
+		return Err(format!("\"{}\" failed with {}", command.join(" "), output.status));
+	}
+	Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Puts `text` on the clipboard.
+pub fn copy(config: &config::Config, text: &str) -> Result<(), String> {
+	let command: Vec<String> = clipboard_command(&config.clipboard_copy_command, WAYLAND_COPY_COMMAND, X11_COPY_COMMAND)?;
+	// the clipboard tools keep running to serve the clipboard, so their output must not be captured
+	let mut child = Command::new(&command[0])
+		.args(&command[1..])
+		.stdin(Stdio::piped())
+		.stdout(Stdio::null())
+		.spawn()
+		.map_err(|e| format!("executing \"{}\" failed with error: \"{}\"", command[0], e))?;
+
+	let mut stdin = child.stdin.take().expect("failed to open the stdin of the clipboard command");
+	stdin.write_all(text.as_bytes()).map_err(|e| e.to_string())?;
+	drop(stdin);
+
+	let status = child.wait().map_err(|e| e.to_string())?;
+	if !status.success() {
+		return Err(format!("\"{}\" failed with {}", command.join(" "), status));
+	}
+	Ok(())
+}
+
+/// Splits the configured command, if none is configured the Wayland tools are used in a Wayland
+/// session and xclip otherwise.
+fn clipboard_command(configured: &str, wayland: &str, x11: &str) -> Result<Vec<String>, String> {
+	let command: &str = match (configured.is_empty(), std::env::var_os("WAYLAND_DISPLAY").is_some()) {
+		(false, _) => { configured }
+		(true, true) => { wayland }
+		(true, false) => { x11 }
+	};
+	shlex::split(command)
+		.filter(|c| !c.is_empty())
+		.ok_or(format!("invalid clipboard command \"{}\"", command))
+}
@@ -0,0 +1,110 @@
+use crate::backup;
+use crate::config;
+use crate::sql;
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+use clap::ValueEnum;
+use clap_complete::engine::CompletionCandidate;
+use clap_complete::env::{self, EnvCompleter};
+use rusqlite::{Connection, OpenFlags};
+
+/// Environment variable which makes schwimark complete the command line instead of running it.
+pub const COMPLETE_VAR: &str = "COMPLETE";
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Shell {
+	Bash,
+	Zsh,
+	Fish,
+	Elvish,
+}
+
+/// Returns the script registering the completions with `shell`. The script runs `name` with `COMPLETE`
+/// set, so mark names, tags, profiles and actions are completed from the database and config at the
+/// time of completing.
+pub fn script(shell: Shell, name: &str) -> Result<String, String> {
+	let completer: &dyn EnvCompleter = match shell {
+		Shell::Bash => { &env::Bash }
+		Shell::Zsh => { &env::Zsh }
+		Shell::Fish => { &env::Fish }
+		Shell::Elvish => { &env::Elvish }
+	};
+	let mut script: Vec<u8> = vec![];
+	completer
+		.write_registration(COMPLETE_VAR, name, name, name, &mut script)
+		.map_err(|e| format!("failed to write the completion script: {}", e))?;
+	String::from_utf8(script).map_err(|e| e.to_string())
+}
+
+pub fn marks() -> Vec<CompletionCandidate> {
+	with_database(|database| {
+		sql::get_all_marks(database).unwrap_or_default().into_iter().map(|(m, _)| m.name().to_string()).collect()
+	})
+}
+
+pub fn trash() -> Vec<CompletionCandidate> {
+	with_database(|database| sql::get_trash(database).unwrap_or_default().into_iter().map(|(_, name)| name).collect())
+}
+
+pub fn tags() -> Vec<CompletionCandidate> {
+	with_database(|database| sql::get_all_tags(database).unwrap_or_default())
+}
+
+pub fn actions() -> Vec<CompletionCandidate> {
+	candidates(load_config().map(|c| c.actions.into_keys().collect()).unwrap_or_default())
+}
+
+pub fn profiles() -> Vec<CompletionCandidate> {
+	candidates(load_config().map(|c| c.profiles.into_keys().collect()).unwrap_or_default())
+}
+
+pub fn backups() -> Vec<CompletionCandidate> {
+	let mut backups: Vec<String> = vec!["latest".to_string()];
+	if let Some(config) = load_config() {
+		backups.extend(backup::Backups::new(&config).list().unwrap_or_default().into_iter().map(|b| b.name));
+	}
+	candidates(backups)
+}
+
+fn candidates(values: Vec<String>) -> Vec<CompletionCandidate> {
+	values.into_iter().map(CompletionCandidate::new).collect()
+}
+
+/// Runs `query` on the database, which is only read so completing never creates or migrates it.
+fn with_database(query: impl Fn(&Connection) -> Vec<String>) -> Vec<CompletionCandidate> {
+	let Some(config) = load_config() else { return vec![]; };
+	match Connection::open_with_flags(&config.database_file, OpenFlags::SQLITE_OPEN_READ_ONLY) {
+		Ok(database) => { candidates(query(&database)) }
+		Err(_) => { vec![] }
+	}
+}
+
+/// Loads the config with the `--config`, `--database` and `--profile` options of the command line
+/// being completed, which the completion script passes after `--`.
+fn load_config() -> Option<config::Config> {
+	let mut words: Vec<OsString> = std::env::args_os().skip_while(|a| a != "--").skip(1).collect();
+	// the word being completed is not a value yet
+	words.pop();
+	let option = |short: &str, long: &str| -> Option<String> {
+		let mut value: Option<String> = None;
+		for (i, word) in words.iter().enumerate() {
+			let word: &str = word.to_str().unwrap_or_default();
+			if word == short || word == long {
+				value = words.get(i + 1).and_then(|v| v.to_str()).map(|v| v.to_string());
+			} else if let Some(v) = word.strip_prefix(long).and_then(|v| v.strip_prefix('=')) {
+				value = Some(v.to_string());
+			}
+		}
+		value
+	};
+
+	let mut config: config::Config = match option("-c", "--config") {
+		Some(config_path) => { config::Config::new(PathBuf::from(config_path)) }
+		None => { config::Config::default() }
+	};
+	config
+		.load_config(option("-p", "--profile"), option("-d", "--database").map(PathBuf::from), None, None, None)
+		.ok()?;
+	Some(config)
+}
@@ -1,69 +1,516 @@
-use std::io::{Read, Write};
-use std::path::PathBuf;
-use std::fs::File;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
 use serde::{Serialize, Deserialize};
 
 const CONFIG_FILE_NAME: &str = "config.toml";
+const DROP_IN_DIRECTORY_NAME: &str = "config.d";
+const INCLUDE_KEY: &str = "include";
 const DATABASE_FILE_NAME: &str = "schwimark.db";
 const DEFAULT_DMENU_COMMAND: &str = "dmenu";
 const DEFAULT_DMENU_MARK_SEARCH_ARGS: &str = "";
 const DEFAULT_DMENU_TAG_SEARCH_ARGS: &str = "";
+const DEFAULT_OPENER_GRACE_PERIOD_MS: u64 = 0;
+const DEFAULT_OPEN_ALL_DELAY_MS: u64 = 0;
+const DEFAULT_OPEN_ALL_CONFIRM_COUNT: usize = 5;
+const DEFAULT_BACKUP_RETENTION: usize = 10;
+const DEFAULT_BACKUP_INTERVAL_HOURS: u64 = 0;
+const DEFAULT_CHECK_CONCURRENCY: usize = 8;
+const DEFAULT_CHECK_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_FETCH_TIMEOUT_SECS: u64 = 5;
+/// Entries of the action menu which are built in, custom actions are added with `[actions.<name>]`.
+pub const BUILTIN_ACTIONS: [&str; 7] = ["open", "open-with", "copy", "show", "edit", "add-tag", "delete"];
+const CONFIG_TEMPLATE: &str = "\
+# SchWiMark configuration
+# Every setting is optional, the commented out values are the defaults.
 
-#[derive(Serialize, Deserialize)]
+# other config files read before this one (relative to this file), *.toml files in the config.d
+# directory next to this file are read before it as well, so this file overrides them
+# include = [\"site.toml\"]
+
+# SchWiMark database (defaults to $XDG_DATA_HOME/schwimark/schwimark.db)
+# database_file = \"/path/to/schwimark.db\"
+
+# command the SchWiMarks are piped to for selecting one, e.g. \"rofi -dmenu\" or \"wofi --dmenu\"
+# dmenu_command = \"dmenu\"
+# additional arguments used when selecting SchWiMarks and tags
+# dmenu_mark_search_args = \"\"
+# dmenu_tag_search_args = \"\"
+
+# milliseconds to wait after launching an application to detect it failing right away
+# opener_grace_period_ms = 0
+# file the output of launched applications is appended to (discarded if empty)
+# opener_log_file = \"\"
+
+# milliseconds to wait between SchWiMarks opened with `open --tag <tag> --all`
+# open_all_delay_ms = 0
+# ask for confirmation before opening more than this many SchWiMarks at once
+# open_all_confirm_count = 5
+
+# directory backups are written to (a `backups` directory next to the database if empty)
+# backup_directory = \"\"
+# number of backups to keep, 0 disables backups
+# backup_retention = 10
+# create a backup on start if the newest one is older than this many hours, 0 disables this
+# backup_interval_hours = 0
+
+# git working tree used by `sync` (a `sync` directory next to the database if empty)
+# sync_directory = \"\"
+# git repository cloned into the sync directory on the first sync
+# sync_remote = \"\"
+
+# number of urls `check` requests at once
+# check_concurrency = 8
+# seconds after which `check` gives up on a url
+# check_timeout_secs = 10
+# seconds to wait for the page when `add` fetches its title and description
+# fetch_timeout_secs = 5
+
+# directory relative paths of SchWiMarks are resolved against, may use ~ and environment variables
+# (the working directory if empty), e.g. \"~\" to share a database between different home directories
+# path_base_directory = \"\"
+
+# directory `archive` stores the snapshots of pages in (an `archive` directory next to the database if empty)
+# archive_directory = \"\"
+
+# commands printing and setting the clipboard (wl-paste/wl-copy in a Wayland session, xclip otherwise if empty)
+# clipboard_paste_command = \"\"
+# clipboard_copy_command = \"\"
+
+# entries of the menu `action` shows after selecting a SchWiMark, in this order
+# action_menu = [\"open\", \"open-with\", \"copy\", \"show\", \"edit\", \"add-tag\", \"delete\"]
+# custom actions, shown in the action menu after the entries above unless listed there and run on the
+# selected SchWiMarks with `run <name>`, the command may use the placeholders {url}, {path}, {name}
+# and {dirname} (the url/path is appended if it uses none)
+# [actions.archive-org]
+# command = \"firefox https://web.archive.org/web/{url}\"
+# with shell = true the command is run by `sh -c` and the placeholders are quoted, so they must not be
+# quoted in the command
+# [actions.qr]
+# command = \"qrencode -t ansiutf8 {url} | less -R\"
+# shell = true
+
+# profiles selected with --profile override the settings above
+# [profiles.work]
+# database_file = \"/path/to/work.db\"
+# dmenu_command = \"dmenu\"
+# dmenu_mark_search_args = \"\"
+# dmenu_tag_search_args = \"\"
+# sync_directory = \"\"
+# sync_remote = \"\"
+";
+
+/// Environment variables with a shorter name than `SCHWIMARK_<SETTING>`.
+const ENV_NAMES: [(&str, &str); 4] = [
+	("database_file", "SCHWIMARK_DATABASE"),
+	("dmenu_command", "SCHWIMARK_MENU"),
+	("dmenu_mark_search_args", "SCHWIMARK_MENU_MARK_ARGS"),
+	("dmenu_tag_search_args", "SCHWIMARK_MENU_TAG_ARGS"),
+];
+
+/// Where the effective value of a setting was taken from.
+#[derive(Clone, PartialEq)]
+pub enum Source {
+	Default,
+	File(PathBuf),
+	Env(String),
+	Profile(String),
+	Flag(&'static str),
+}
+
+impl fmt::Display for Source {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Source::Default => { write!(f, "default") }
+			Source::File(path) => { write!(f, "file {}", path.display()) }
+			Source::Env(name) => { write!(f, "environment {}", name) }
+			Source::Profile(name) => { write!(f, "profile {}", name) }
+			Source::Flag(flag) => { write!(f, "flag {}", flag) }
+		}
+	}
+}
+
+/// Settings of one source, applied over the settings of the sources before it.
+struct Layer {
+	source: Source,
+	settings: toml::Table,
+}
+
+/// Other config files read before the file containing `include = [...]`, relative to its directory.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Includes {
+	include: Vec<PathBuf>,
+}
+
+/// Command defined by the user which can be run on a SchWiMark from the action menu.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Action {
+	/// command line with the placeholders {url}, {path}, {name} and {dirname}
+	pub command: String,
+	/// run the command with `sh -c`, the placeholders are quoted for the shell
+	pub shell: bool,
+}
+
+/// Named set of settings selected with `--profile`, overriding the top level settings of the config.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub database_file: Option<PathBuf>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub dmenu_command: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub dmenu_mark_search_args: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub dmenu_tag_search_args: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub sync_directory: Option<PathBuf>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub sync_remote: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 #[serde[default]]
 pub struct Config {
 	#[serde(skip)]
 	config_file: PathBuf,
+	/// name of the profile the settings were taken from (None for the top level settings)
+	#[serde(skip)]
+	pub profile: Option<String>,
+	/// source of every setting which is not a default
+	#[serde(skip)]
+	sources: BTreeMap<String, Source>,
+	/// `file:line:column` of the keys in the config files, profile keys as `profiles.<name>.<key>`
+	#[serde(skip)]
+	positions: BTreeMap<String, String>,
+	/// config files which were read, lowest priority first
+	#[serde(skip)]
+	loaded_files: Vec<PathBuf>,
 
 	pub database_file: PathBuf,
 	pub dmenu_command: String,
 	pub dmenu_mark_search_args: String,
 	pub dmenu_tag_search_args: String,
+	/// milliseconds to wait after launching an application to detect it failing right away
+	pub opener_grace_period_ms: u64,
+	/// file the output of launched applications is appended to (discarded if empty)
+	pub opener_log_file: PathBuf,
+	/// milliseconds to wait between SchWiMarks opened with `open --tag <tag> --all`
+	pub open_all_delay_ms: u64,
+	/// ask for confirmation before opening more than this many SchWiMarks at once
+	pub open_all_confirm_count: usize,
+	/// directory backups are written to (a `backups` directory next to the database if empty)
+	pub backup_directory: PathBuf,
+	/// number of backups to keep, 0 disables backups
+	pub backup_retention: usize,
+	/// create a backup on start if the newest one is older than this many hours, 0 disables this
+	pub backup_interval_hours: u64,
+	/// git working tree used by `sync` (a `sync` directory next to the database if empty)
+	pub sync_directory: PathBuf,
+	/// git repository cloned into the sync directory on the first sync (a local repository only if empty)
+	pub sync_remote: String,
+	/// number of urls `check` requests at once
+	pub check_concurrency: usize,
+	/// seconds after which `check` gives up on a url
+	pub check_timeout_secs: u64,
+	/// seconds to wait for the page when `add` fetches its title and description
+	pub fetch_timeout_secs: u64,
+	/// directory relative paths of SchWiMarks are resolved against (the working directory if empty)
+	pub path_base_directory: PathBuf,
+	/// directory `archive` stores the snapshots of pages in (an `archive` directory next to the database if empty)
+	pub archive_directory: PathBuf,
+	/// command printing the clipboard (wl-paste in a Wayland session, xclip otherwise if empty)
+	pub clipboard_paste_command: String,
+	/// command setting the clipboard to its stdin (wl-copy in a Wayland session, xclip otherwise if empty)
+	pub clipboard_copy_command: String,
+	/// entries of the menu `action` shows after selecting a SchWiMark, in this order
+	pub action_menu: Vec<String>,
+	#[serde(skip_serializing_if = "BTreeMap::is_empty")]
+	pub actions: BTreeMap<String, Action>,
+	#[serde(skip_serializing_if = "BTreeMap::is_empty")]
+	pub profiles: BTreeMap<String, Profile>,
 }
 
 impl Config {
-	pub fn new(config_file: PathBuf) -> Config { 
+	pub fn new(config_file: PathBuf) -> Config {
 		Config {
-			config_file: config_file,
-			database_file: get_default_database_file(),
-			dmenu_command: DEFAULT_DMENU_COMMAND.to_string(),
-			dmenu_mark_search_args: DEFAULT_DMENU_MARK_SEARCH_ARGS.to_string(),
-			dmenu_tag_search_args: DEFAULT_DMENU_TAG_SEARCH_ARGS.to_string(),
+			config_file,
+			..Config::default()
 		}
 	}
 
+	/// Resolves the settings from the layers default, config files, environment, profile and command
+	/// line flags, each overriding the ones before it, and validates the result. Returns all errors found.
 	pub fn load_config(
-		&mut self, database_file: Option<PathBuf>,
+		&mut self,
+		profile: Option<String>,
+		database_file: Option<PathBuf>,
 		dmenu_command: Option<String>,
 		mark_args: Option<String>,
 		tag_args: Option<String>,
-		) {
+		) -> Result<(), String> {
 		assert!(self.config_file.to_str().unwrap() != "");
-		let mut config_file: File = File::options()
-			.read(true)
-			.write(true)
-			.create(true)
-			.append(true)
-			.open(&self.config_file)
-			.expect("failed to open the config file");
+		for file_layer in self.read_config_files()? {
+			self.apply_layer(file_layer)?;
+		}
+		for env_layer in env_layers()? {
+			self.apply_layer(env_layer)?;
+		}
+
+		if let Some(profile) = profile {
+			*self = self.with_profile(&profile)?;
+		}
+
+		let mut flags: Vec<(&str, &'static str, toml::Value)> = vec![];
+		if let Some(d) = database_file { flags.push(("database_file", "--database", toml::Value::from(d.to_string_lossy().to_string()))); }
+		if let Some(d) = dmenu_command { flags.push(("dmenu_command", "--dmenu-command", toml::Value::from(d))); }
+		if let Some(m) = mark_args { flags.push(("dmenu_mark_search_args", "--dmenu-mark-arguments", toml::Value::from(m))); }
+		if let Some(t) = tag_args { flags.push(("dmenu_tag_search_args", "--dmenu-tag-arguments", toml::Value::from(t))); }
+		for (key, flag, value) in flags {
+			self.apply_layer(Layer {
+				source: Source::Flag(flag),
+				settings: toml::Table::from_iter([(key.to_string(), value)]),
+			})?;
+		}
+
+		let errors: Vec<String> = self.validate();
+		if !errors.is_empty() { return Err(errors.join("\n")); }
+		Ok(())
+	}
+
+	/// Reads the system wide configs in `$XDG_CONFIG_DIRS` followed by the config file, each together
+	/// with its includes and drop-ins, as layers ordered from lowest to highest priority.
+	fn read_config_files(&mut self) -> Result<Vec<Layer>, String> {
+		let mut layers: Vec<Layer> = vec![];
+		let mut visited: Vec<PathBuf> = vec![];
+
+		let system_files: Vec<PathBuf> = xdg::BaseDirectories::with_prefix("schwimark")
+			.get_config_dirs()
+			.into_iter()
+			.rev()
+			.map(|d| d.join(CONFIG_FILE_NAME))
+			.collect();
+		for file in system_files {
+			self.read_config_tree(&file, true, &mut visited, &mut layers)?;
+		}
+		let config_file: PathBuf = self.config_file.clone();
+		self.read_config_tree(&config_file, true, &mut visited, &mut layers)?;
+		Ok(layers)
+	}
+
+	/// Reads a config file after the files it includes and, if `drop_ins` is set, the `*.toml` files of the
+	/// `config.d` directory next to it in lexical order, so the file overrides them. A missing file has no settings.
+	fn read_config_tree(&mut self, file: &Path, drop_ins: bool, visited: &mut Vec<PathBuf>, layers: &mut Vec<Layer>) -> Result<(), String> {
+		let canonical: PathBuf = fs::canonicalize(file).unwrap_or(file.to_path_buf());
+		if visited.contains(&canonical) { return Ok(()); }
+		visited.push(canonical);
+		let directory: PathBuf = file.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+
+		let contents: String = match fs::read_to_string(file) {
+			Ok(c) => { c }
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => { String::new() }
+			Err(e) => { return Err(format!("failed to read the config file \"{}\": {}", file.display(), e)); }
+		};
+
+		// parsed as a Config first to report wrong types with their line and column
+		toml::from_str::<Config>(&contents)
+			.map_err(|e| format!("{}: {}", file.display(), e))?;
+		let includes: Includes = toml::from_str(&contents)
+			.map_err(|e| format!("{}: {}", file.display(), e))?;
+		let mut settings: toml::Table = toml::from_str(&contents)
+			.map_err(|e| format!("{}: {}", file.display(), e))?;
+		settings.remove(INCLUDE_KEY);
+
+		for include in includes.include {
+			let include: PathBuf = directory.join(expand_home(&include));
+			if !include.is_file() {
+				return Err(format!("{}: the included file \"{}\" does not exist", file.display(), include.display()));
+			}
+			self.read_config_tree(&include, false, visited, layers)?;
+		}
+
+		let drop_in_directory: PathBuf = directory.join(DROP_IN_DIRECTORY_NAME);
+		if drop_ins && drop_in_directory.is_dir() {
+			let mut drop_in_files: Vec<PathBuf> = fs::read_dir(&drop_in_directory)
+				.map_err(|e| format!("failed to read \"{}\": {}", drop_in_directory.display(), e))?
+				.filter_map(|e| e.ok())
+				.map(|e| e.path())
+				.filter(|p| p.is_file() && p.extension().is_some_and(|e| e == "toml"))
+				.collect();
+			drop_in_files.sort();
+			for drop_in in drop_in_files {
+				self.read_config_tree(&drop_in, false, visited, layers)?;
+			}
+		}
+
+		if contents.is_empty() && !file.exists() { return Ok(()); }
+		for (key, (line, column)) in key_positions(&contents) {
+			self.positions.insert(key, format!("{}:{}:{}", file.display(), line, column));
+		}
+		self.loaded_files.push(file.to_path_buf());
+		layers.push(Layer {
+			source: Source::File(file.to_path_buf()),
+			settings,
+		});
+		Ok(())
+	}
+
+	/// Overrides the settings contained in the layer and records the layer as their source.
+	fn apply_layer(&mut self, layer: Layer) -> Result<(), String> {
+		let mut table: toml::Table = toml::Table::try_from(&*self).expect("the config can be serialized");
+		for key in layer.settings.keys().filter(|k| *k != "profiles") {
+			self.sources.insert(key.clone(), layer.source.clone());
+		}
+		merge_tables(&mut table, layer.settings);
+
+		// every setting is taken from the merged table, only the state which is not a setting is carried over
+		let mut applied: Config = table.try_into().map_err(|e| format!("{}: {}", layer.source, e))?;
+		applied.config_file = std::mem::take(&mut self.config_file);
+		applied.profile = self.profile.take();
+		applied.sources = std::mem::take(&mut self.sources);
+		applied.positions = std::mem::take(&mut self.positions);
+		applied.loaded_files = std::mem::take(&mut self.loaded_files);
+		*self = applied;
+		Ok(())
+	}
+
+	pub fn config_file(&self) -> &Path {
+		&self.config_file
+	}
+
+	/// Config files which were read, lowest priority first.
+	pub fn loaded_files(&self) -> &[PathBuf] {
+		&self.loaded_files
+	}
+
+	/// Where the effective value of a setting was taken from.
+	pub fn source(&self, key: &str) -> Source {
+		self.sources.get(key).cloned().unwrap_or(Source::Default)
+	}
+
+	/// Describes where a setting was set for error messages, e.g. `config.toml:3:1` or `flag --database`.
+	fn location(&self, key: &str) -> String {
+		let position: Option<&String> = match self.source(key) {
+			Source::File(_) => { self.positions.get(key) }
+			Source::Profile(name) => { self.positions.get(&format!("profiles.{}.{}", name, key)) }
+			source => { return source.to_string(); }
+		};
+		position.cloned().unwrap_or(self.source(key).to_string())
+	}
 
-		let mut config_contents = String::new();
-		config_file.read_to_string(&mut config_contents)
-			.expect("failed to write config file to string");
+	/// Checks the settings for errors which would otherwise only show up when they are used.
+	pub fn validate(&self) -> Vec<String> {
+		let mut errors: Vec<String> = vec![];
+		let known_keys: Vec<String> = known_keys();
+		for (key, position) in self.positions.iter() {
+			if key.starts_with("profiles.") || known_keys.contains(key) { continue; }
+			errors.push(format!("{}: unknown setting \"{}\"", position, key));
+		}
+
+		if self.database_file.as_os_str().is_empty() {
+			errors.push(format!("{}: database_file cannot be empty", self.location("database_file")));
+		}
+		match shlex::split(&self.dmenu_command) {
+			None => { errors.push(format!("{}: invalid quoting in dmenu_command", self.location("dmenu_command"))); }
+			Some(c) if c.is_empty() => { errors.push(format!("{}: dmenu_command cannot be empty", self.location("dmenu_command"))); }
+			Some(_) => {}
+		}
+		for (key, value) in [
+			("dmenu_mark_search_args", &self.dmenu_mark_search_args),
+			("dmenu_tag_search_args", &self.dmenu_tag_search_args),
+			("clipboard_paste_command", &self.clipboard_paste_command),
+			("clipboard_copy_command", &self.clipboard_copy_command),
+		] {
+			if shlex::split(value).is_none() {
+				errors.push(format!("{}: invalid quoting in {}", self.location(key), key));
+			}
+		}
+		for entry in self.action_menu.iter() {
+			if !BUILTIN_ACTIONS.contains(&entry.as_str()) && !self.actions.contains_key(entry) {
+				errors.push(format!("{}: unknown action \"{}\" in action_menu", self.location("action_menu"), entry));
+			}
+		}
+		for (name, action) in self.actions.iter() {
+			if BUILTIN_ACTIONS.contains(&name.as_str()) {
+				errors.push(format!("{}: the action \"{}\" is built in", self.location("actions"), name));
+			}
+			match shlex::split(&action.command) {
+				None if !action.shell => { errors.push(format!("{}: invalid quoting in the command of action \"{}\"", self.location("actions"), name)); }
+				Some(c) if c.is_empty() => { errors.push(format!("{}: the command of action \"{}\" is empty", self.location("actions"), name)); }
+				_ => {}
+			}
+		}
+		if self.check_concurrency == 0 {
+			errors.push(format!("{}: check_concurrency must be at least 1", self.location("check_concurrency")));
+		}
+
+		for (name, profile) in self.profiles.iter() {
+			let position = |key: &str| -> String {
+				self.positions
+					.get(&format!("profiles.{}.{}", name, key))
+					.cloned()
+					.unwrap_or(format!("profile {}", name))
+			};
+			for (key, value) in [
+				("dmenu_command", &profile.dmenu_command),
+				("dmenu_mark_search_args", &profile.dmenu_mark_search_args),
+				("dmenu_tag_search_args", &profile.dmenu_tag_search_args),
+			] {
+				if value.as_ref().is_some_and(|v| shlex::split(v).is_none()) {
+					errors.push(format!("{}: invalid quoting in {} of profile {}", position(key), key, name));
+				}
+			}
+			if profile.database_file.as_ref().is_some_and(|d| d.as_os_str().is_empty()) {
+				errors.push(format!("{}: database_file of profile {} cannot be empty", position("database_file"), name));
+			}
+		}
+		errors
+	}
 
-		if config_contents.is_empty() {
-			let default_config_contents: &str = &toml::to_string(&self).unwrap();
-			config_file.write(default_config_contents.as_bytes()).unwrap();
+	/// Returns every setting with its effective value rendered as TOML and its source.
+	pub fn settings(&self) -> Vec<(String, String, Source)> {
+		let table: toml::Table = toml::Table::try_from(self).expect("the config can be serialized");
+		table
+			.into_iter()
+			.filter(|(key, _)| key != "profiles")
+			.map(|(key, value)| {
+				let source: Source = self.source(&key);
+				(key, value.to_string(), source)
+			})
+			.collect()
+	}
+
+	/// Writes a commented template to the config file, refuses to overwrite an existing config.
+	pub fn write_template(&self, force: bool) -> Result<(), String> {
+		let exists: bool = fs::metadata(&self.config_file).is_ok_and(|m| m.len() > 0);
+		if exists && !force {
+			return Err(format!("the config file \"{}\" already exists (use --force to overwrite it)", self.config_file.display()));
+		}
+		if let Some(parent) = self.config_file.parent().filter(|p| !p.as_os_str().is_empty()) {
+			fs::create_dir_all(parent).map_err(|e| format!("failed to create \"{}\": {}", parent.display(), e))?;
 		}
+		fs::write(&self.config_file, CONFIG_TEMPLATE)
+			.map_err(|e| format!("failed to write the config file \"{}\": {}", self.config_file.display(), e))
+	}
 
-		let read_config: Config = toml::from_str(&config_contents)
-			.expect("failed to parse config file");
-		self.database_file = database_file.unwrap_or(read_config.database_file);
-		self.dmenu_command = dmenu_command.unwrap_or(read_config.dmenu_command);
-		self.dmenu_mark_search_args = mark_args.unwrap_or(read_config.dmenu_mark_search_args);
-		self.dmenu_tag_search_args = tag_args.unwrap_or(read_config.dmenu_tag_search_args);
+	/// Returns a copy of the config with the settings of the named profile applied.
+	pub fn with_profile(&self, name: &str) -> Result<Config, String> {
+		let profile: &Profile = self.profiles.get(name).ok_or(format!(
+			"unknown profile \"{}\" (defined profiles: {})",
+			name,
+			self.profiles.keys().cloned().collect::<Vec<String>>().join(", "),
+		))?;
 
-		assert!(self.database_file.to_str().unwrap() != "");
+		let mut config: Config = self.clone();
+		config.profile = Some(name.to_string());
+		config.apply_layer(Layer {
+			source: Source::Profile(name.to_string()),
+			settings: toml::Table::try_from(profile).expect("the profile can be serialized"),
+		})?;
+		Ok(config)
 	}
 }
 
@@ -71,26 +518,146 @@ impl Default for Config {
 	fn default() -> Config {
 		Config {
 			config_file: get_default_config_file(),
+			profile: None,
+			sources: BTreeMap::new(),
+			positions: BTreeMap::new(),
+			loaded_files: vec![],
 			database_file: get_default_database_file(),
 			dmenu_command: DEFAULT_DMENU_COMMAND.to_string(),
 			dmenu_mark_search_args: DEFAULT_DMENU_MARK_SEARCH_ARGS.to_string(),
-			dmenu_tag_search_args: DEFAULT_DMENU_MARK_SEARCH_ARGS.to_string(),
+			dmenu_tag_search_args: DEFAULT_DMENU_TAG_SEARCH_ARGS.to_string(),
+			opener_grace_period_ms: DEFAULT_OPENER_GRACE_PERIOD_MS,
+			opener_log_file: PathBuf::new(),
+			open_all_delay_ms: DEFAULT_OPEN_ALL_DELAY_MS,
+			open_all_confirm_count: DEFAULT_OPEN_ALL_CONFIRM_COUNT,
+			backup_directory: PathBuf::new(),
+			backup_retention: DEFAULT_BACKUP_RETENTION,
+			backup_interval_hours: DEFAULT_BACKUP_INTERVAL_HOURS,
+			sync_directory: PathBuf::new(),
+			sync_remote: String::new(),
+			check_concurrency: DEFAULT_CHECK_CONCURRENCY,
+			check_timeout_secs: DEFAULT_CHECK_TIMEOUT_SECS,
+			fetch_timeout_secs: DEFAULT_FETCH_TIMEOUT_SECS,
+			path_base_directory: PathBuf::new(),
+			archive_directory: PathBuf::new(),
+			clipboard_paste_command: String::new(),
+			clipboard_copy_command: String::new(),
+			action_menu: BUILTIN_ACTIONS.iter().map(|a| a.to_string()).collect(),
+			actions: BTreeMap::new(),
+			profiles: BTreeMap::new(),
+		}
+	}
+}
+
+/// Name of the environment variable overriding a setting.
+fn env_name(key: &str) -> String {
+	ENV_NAMES
+		.iter()
+		.find(|(k, _)| *k == key)
+		.map(|(_, name)| name.to_string())
+		.unwrap_or(format!("SCHWIMARK_{}", key.to_uppercase()))
+}
+
+/// Reads the `SCHWIMARK_*` environment variables, one layer per variable. Empty variables are ignored.
+fn env_layers() -> Result<Vec<Layer>, String> {
+	let defaults: toml::Table = toml::Table::try_from(Config::new(PathBuf::new())).expect("the config can be serialized");
+	let mut layers: Vec<Layer> = vec![];
+
+	for (key, default) in defaults.iter() {
+		let name: String = env_name(key);
+		let value: String = match std::env::var(&name) {
+			Ok(v) if !v.is_empty() => { v }
+			Ok(_) | Err(std::env::VarError::NotPresent) => { continue; }
+			Err(e) => { return Err(format!("environment {}: {}", name, e)); }
+		};
+		let value: toml::Value = match default {
+			toml::Value::Integer(_) => {
+				toml::Value::Integer(value.trim().parse::<i64>()
+					.map_err(|_| format!("environment {}: expected a number but got \"{}\"", name, value))?)
+			}
+			toml::Value::Array(_) => {
+				toml::Value::Array(value.split(',').map(|v| toml::Value::String(v.trim().to_string())).collect())
+			}
+			_ => { toml::Value::String(value) }
+		};
+		layers.push(Layer {
+			source: Source::Env(name),
+			settings: toml::Table::from_iter([(key.clone(), value)]),
+		});
+	}
+	Ok(layers)
+}
+
+/// Names of all top level settings.
+fn known_keys() -> Vec<String> {
+	let mut keys: Vec<String> = toml::Table::try_from(Config::new(PathBuf::new()))
+		.expect("the config can be serialized")
+		.keys()
+		.cloned()
+		.collect();
+	keys.push("profiles".to_string());
+	keys.push("actions".to_string());
+	keys.push(INCLUDE_KEY.to_string());
+	keys
+}
+
+/// Overrides the values of `base` with the ones of `overlay`, tables (the profiles) are merged key by key.
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+	for (key, value) in overlay {
+		match (base.get_mut(&key), value) {
+			(Some(toml::Value::Table(base_table)), toml::Value::Table(overlay_table)) => { merge_tables(base_table, overlay_table); }
+			(_, value) => { base.insert(key, value); }
+		}
+	}
+}
+
+/// Expands a leading `~` to the home directory.
+fn expand_home(path: &Path) -> PathBuf {
+	match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+		(Ok(rest), Some(home)) => { PathBuf::from(home).join(rest) }
+		_ => { path.to_path_buf() }
+	}
+}
+
+/// Finds the line and column (starting at 1) of every top level key and every key of a profile.
+fn key_positions(contents: &str) -> BTreeMap<String, (usize, usize)> {
+	let mut positions: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+	let table = match toml::de::DeTable::parse(contents) {
+		Ok(t) => { t }
+		Err(_) => { return positions; }
+	};
+
+	for (key, value) in table.get_ref().iter() {
+		positions.insert(key.get_ref().to_string(), line_column(contents, key.span().start));
+		if key.get_ref() != "profiles" { continue; }
+		let Some(profiles) = value.get_ref().as_table() else { continue; };
+		for (name, profile) in profiles.iter() {
+			let Some(profile) = profile.get_ref().as_table() else { continue; };
+			for (profile_key, _) in profile.iter() {
+				positions.insert(
+					format!("profiles.{}.{}", name.get_ref(), profile_key.get_ref()),
+					line_column(contents, profile_key.span().start),
+				);
+			}
 		}
 	}
+	positions
 }
 
+fn line_column(contents: &str, offset: usize) -> (usize, usize) {
+	let before: &str = &contents[..offset.min(contents.len())];
+	let line: usize = before.matches('\n').count() + 1;
+	let column: usize = before.rsplit('\n').next().map(|l| l.chars().count()).unwrap_or(0) + 1;
+	(line, column)
+}
 
 fn get_default_config_file() -> PathBuf {
 	let xdg_dirs = xdg::BaseDirectories::with_prefix("schwimark");
 	let config_path: PathBuf = xdg_dirs
-		.find_config_file(CONFIG_FILE_NAME)
-		.unwrap_or(
-			xdg_dirs
-				.place_config_file(CONFIG_FILE_NAME)
-				.expect("failed to aquire a default config path")
-		);
+		.get_config_file(CONFIG_FILE_NAME)
+		.expect("failed to aquire a default config path");
 
-	return config_path
+	config_path
 }
 
 fn get_default_database_file() -> PathBuf {
@@ -103,5 +670,152 @@ fn get_default_database_file() -> PathBuf {
 				.expect("failed to aquire a default database path")
 		);
 
-	return database_path
+	database_path
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::Mutex;
+
+	/// Held by the tests which read the environment, so they never see the variables another test sets.
+	static ENVIRONMENT: Mutex<()> = Mutex::new(());
+
+	fn load(config_file: &Path) -> Result<Config, String> {
+		let mut config: Config = Config::new(config_file.to_path_buf());
+		config.load_config(None, None, None, None, None)?;
+		Ok(config)
+	}
+
+	fn layer(source: Source, settings: &str) -> Layer {
+		Layer { source, settings: toml::from_str(settings).unwrap() }
+	}
+
+	#[test]
+	fn layers_override_settings_and_record_their_source() {
+		let mut config: Config = Config::new(PathBuf::from("/etc/schwimark/config.toml"));
+		let file: PathBuf = PathBuf::from("config.toml");
+		config.apply_layer(layer(Source::File(file.clone()), "dmenu_command = \"rofi -dmenu\"\ncheck_concurrency = 2\n[profiles.work]\ndmenu_command = \"wofi\"")).unwrap();
+		config.apply_layer(layer(Source::Env("SCHWIMARK_CHECK_CONCURRENCY".to_string()), "check_concurrency = 4")).unwrap();
+
+		assert_eq!(config.config_file(), Path::new("/etc/schwimark/config.toml"));
+		assert_eq!(config.dmenu_command, "rofi -dmenu");
+		assert_eq!(config.check_concurrency, 4);
+		assert_eq!(config.backup_retention, DEFAULT_BACKUP_RETENTION);
+		assert!(config.source("dmenu_command") == Source::File(file));
+		assert!(config.source("check_concurrency") == Source::Env("SCHWIMARK_CHECK_CONCURRENCY".to_string()));
+		assert!(config.source("backup_retention") == Source::Default);
+
+		let work: Config = config.with_profile("work").unwrap();
+		assert_eq!(work.profile.as_deref(), Some("work"));
+		assert_eq!(work.dmenu_command, "wofi");
+		assert_eq!(work.check_concurrency, 4);
+		assert!(work.source("dmenu_command") == Source::Profile("work".to_string()));
+	}
+
+	#[test]
+	fn a_layer_with_a_wrong_type_is_rejected() {
+		let mut config: Config = Config::default();
+		assert!(config.apply_layer(layer(Source::Default, "check_concurrency = \"many\"")).is_err());
+		assert_eq!(config.check_concurrency, DEFAULT_CHECK_CONCURRENCY);
+	}
+
+	#[test]
+	fn environment_variables_override_the_config_file() {
+		let _environment = ENVIRONMENT.lock().unwrap_or_else(|e| e.into_inner());
+		let directory: tempfile::TempDir = tempfile::tempdir().unwrap();
+		let config_file: PathBuf = directory.path().join("config.toml");
+		fs::write(&config_file, "dmenu_command = \"dmenu -i\"\ncheck_concurrency = 2\nfetch_timeout_secs = 3\n").unwrap();
+
+		std::env::set_var("SCHWIMARK_MENU", "wofi --dmenu");
+		std::env::set_var("SCHWIMARK_CHECK_CONCURRENCY", " 8 ");
+		std::env::set_var("SCHWIMARK_ACTION_MENU", "open, copy");
+		std::env::set_var("SCHWIMARK_FETCH_TIMEOUT_SECS", "");
+		let config: Result<Config, String> = load(&config_file);
+		std::env::set_var("SCHWIMARK_CHECK_CONCURRENCY", "many");
+		let invalid: Result<Config, String> = load(&config_file);
+		for name in ["SCHWIMARK_MENU", "SCHWIMARK_CHECK_CONCURRENCY", "SCHWIMARK_ACTION_MENU", "SCHWIMARK_FETCH_TIMEOUT_SECS"] {
+			std::env::remove_var(name);
+		}
+
+		let config: Config = config.unwrap();
+		assert_eq!(config.dmenu_command, "wofi --dmenu");
+		assert!(config.source("dmenu_command") == Source::Env("SCHWIMARK_MENU".to_string()));
+		assert_eq!(config.check_concurrency, 8);
+		assert_eq!(config.action_menu, ["open", "copy"]);
+		// empty variables are ignored
+		assert_eq!(config.fetch_timeout_secs, 3);
+		assert!(config.source("fetch_timeout_secs") == Source::File(config_file));
+		assert_eq!(invalid.err().unwrap(), "environment SCHWIMARK_CHECK_CONCURRENCY: expected a number but got \"many\"");
+	}
+
+	#[test]
+	fn includes_and_drop_ins_are_overridden_by_the_file() {
+		let _environment = ENVIRONMENT.lock().unwrap_or_else(|e| e.into_inner());
+		let directory: tempfile::TempDir = tempfile::tempdir().unwrap();
+		let file = |name: &str, contents: &str| -> PathBuf {
+			let path: PathBuf = directory.path().join(name);
+			fs::create_dir_all(path.parent().unwrap()).unwrap();
+			fs::write(&path, contents).unwrap();
+			path
+		};
+		let site: PathBuf = file("site.toml", "sync_remote = \"site\"\nopen_all_delay_ms = 1\n");
+		let second: PathBuf = file("config.d/20-b.toml", "fetch_timeout_secs = 20\ncheck_timeout_secs = 20\n");
+		let first: PathBuf = file("config.d/10-a.toml", "fetch_timeout_secs = 10\nopen_all_delay_ms = 10\nsync_remote = \"a\"\n");
+		file("config.d/notes.txt", "not a config");
+		let config_file: PathBuf = file("config.toml", "include = [\"site.toml\"]\ncheck_timeout_secs = 30\n");
+
+		let config: Config = load(&config_file).unwrap();
+		assert_eq!(config.sync_remote, "a");
+		assert_eq!(config.open_all_delay_ms, 10);
+		assert_eq!(config.fetch_timeout_secs, 20);
+		assert_eq!(config.check_timeout_secs, 30);
+		let loaded: Vec<&PathBuf> = config.loaded_files().iter().filter(|f| f.starts_with(directory.path())).collect();
+		assert_eq!(loaded, [&site, &first, &second, &config_file]);
+		assert!(config.source("fetch_timeout_secs") == Source::File(second));
+	}
+
+	#[test]
+	fn include_cycles_are_read_once_and_missing_includes_are_errors() {
+		let _environment = ENVIRONMENT.lock().unwrap_or_else(|e| e.into_inner());
+		let directory: tempfile::TempDir = tempfile::tempdir().unwrap();
+		let config_file: PathBuf = directory.path().join("config.toml");
+		fs::write(&config_file, "include = [\"other.toml\"]\nfetch_timeout_secs = 1\n").unwrap();
+		fs::write(directory.path().join("other.toml"), "include = [\"./config.toml\"]\nfetch_timeout_secs = 2\ncheck_timeout_secs = 2\n").unwrap();
+
+		let config: Config = load(&config_file).unwrap();
+		assert_eq!((config.fetch_timeout_secs, config.check_timeout_secs), (1, 2));
+		assert_eq!(config.loaded_files().iter().filter(|f| f.starts_with(directory.path())).count(), 2);
+
+		fs::write(&config_file, "include = [\"missing.toml\"]\n").unwrap();
+		assert_eq!(
+			load(&config_file).err().unwrap(),
+			format!("{}: the included file \"{}\" does not exist", config_file.display(), directory.path().join("missing.toml").display()),
+		);
+	}
+
+	#[test]
+	fn errors_point_at_the_file_and_line_of_the_key() {
+		let _environment = ENVIRONMENT.lock().unwrap_or_else(|e| e.into_inner());
+		let directory: tempfile::TempDir = tempfile::tempdir().unwrap();
+		let config_file: PathBuf = directory.path().join("config.toml");
+		fs::write(&config_file, "# settings\nfetch_timeout_secs = 1\n\n[profiles.work]\n  dmenu_mark_search_args = \"-p 'x\"\n").unwrap();
+		fs::create_dir(directory.path().join("config.d")).unwrap();
+		let drop_in: PathBuf = directory.path().join("config.d").join("local.toml");
+		fs::write(&drop_in, "check_timeout_secs = 5\n  check_concurrency = 0\ncolour = true\n").unwrap();
+
+		let errors: String = load(&config_file).err().unwrap();
+		assert_eq!(errors.lines().collect::<Vec<&str>>(), [
+			format!("{}:3:1: unknown setting \"colour\"", drop_in.display()),
+			format!("{}:2:3: check_concurrency must be at least 1", drop_in.display()),
+			format!("{}:5:3: invalid quoting in dmenu_mark_search_args of profile work", config_file.display()),
+		]);
+
+		fs::write(&drop_in, "check_timeout_secs = 5\n").unwrap();
+		fs::write(&config_file, "# settings\nfetch_timeout_secs = 1\n").unwrap();
+		let config: Config = load(&config_file).unwrap();
+		assert_eq!(config.location("fetch_timeout_secs"), format!("{}:2:1", config_file.display()));
+		assert_eq!(config.location("check_timeout_secs"), format!("{}:1:1", drop_in.display()));
+		assert_eq!(config.location("check_concurrency"), "default");
+	}
 }
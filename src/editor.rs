@@ -0,0 +1,275 @@
+use crate::sql;
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use rusqlite::Connection;
+use serde::{Serialize, Deserialize};
+use tempfile::NamedTempFile;
+
+const DEFAULT_EDITOR: &str = "vi";
+const ERROR_PREFIX: &str = "# error: ";
+const DOCUMENT_HEADER: &str = "\
+# Edit the SchWiMarks below, then save and close the editor to apply the changes.
+# The id identifies a SchWiMark and must not be changed. Removing a [[mark]] block leaves it untouched.
+# Name and url/path must not be empty. Lines starting with # are ignored.
+# Exit the editor with an error (e.g. :cq in vim) to discard all changes.
+";
+
+#[derive(Serialize, Deserialize, PartialEq)]
+struct EditedMark {
+	id: i64,
+	name: String,
+	description: String,
+	url: String,
+	application: String,
+	tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EditDocument {
+	#[serde(default)]
+	mark: Vec<EditedMark>,
+}
+
+/// Renders the given SchWiMarks to a temporary TOML file, opens it in `$VISUAL`/`$EDITOR` and
+/// applies the changed fields in a single transaction. If the result cannot be parsed or applied
+/// the editor is opened again with the error prepended as a comment.
+/// Returns the number of SchWiMarks which were changed.
+pub fn edit_marks(database: &Connection, ids: &[i64]) -> Result<usize, String> {
+	let mut originals: Vec<EditedMark> = Vec::with_capacity(ids.len());
+	for id in ids {
+		let mark: sql::SchWiMark = sql::get_mark(database, *id).map_err(|e| e.to_string())?;
+		originals.push(EditedMark {
+			id: mark.id(),
+			name: mark.name().to_string(),
+			description: mark.description().to_string(),
+			url: mark.url().to_string(),
+			application: mark.application().to_string(),
+			tags: sql::get_tags(database, *id).map_err(|e| e.to_string())?,
+		});
+	}
+
+	let document: EditDocument = EditDocument { mark: originals };
+	let rendered: String = toml::to_string(&document).map_err(|e| e.to_string())?;
+	let mut contents: String = format!("{}\n{}", DOCUMENT_HEADER, rendered);
+
+	// created exclusively with a random name and only readable by the user, it is removed when dropped
+	let temp_file: NamedTempFile = tempfile::Builder::new()
+		.prefix("schwimark-edit-")
+		.suffix(".toml")
+		.tempfile()
+		.map_err(|e| format!("failed to create the file to edit: {}", e))?;
+	let edit_file: &Path = temp_file.path();
+	loop {
+		fs::write(edit_file, &contents).map_err(|e| e.to_string())?;
+		run_editor(edit_file)?;
+		let edited: String = fs::read_to_string(edit_file).map_err(|e| e.to_string())?;
+		let edited: String = strip_errors(&edited);
+
+		match parse_document(&edited, &document.mark).and_then(|changes| apply_changes(database, &document.mark, &changes)) {
+			Ok(changed) => { return Ok(changed); }
+			Err(e) if contents.starts_with(ERROR_PREFIX) && strip_errors(&contents) == edited => {
+				// the file was saved unchanged after an error, give up instead of asking forever
+				return Err(e);
+			}
+			Err(e) => {
+				let error_comment: String = e.lines().map(|l| format!("{}{}\n", ERROR_PREFIX, l)).collect();
+				contents = format!("{}{}", error_comment, edited);
+			}
+		}
+	}
+}
+
+fn run_editor(file: &Path) -> Result<(), String> {
+	let editor: String = std::env::var("VISUAL")
+		.or_else(|_| std::env::var("EDITOR"))
+		.ok()
+		.filter(|e| !e.is_empty())
+		.unwrap_or(DEFAULT_EDITOR.to_string());
+	let mut command: Vec<String> = shlex::split(&editor).ok_or(format!("invalid quoting in editor \"{}\"", editor))?;
+	if command.is_empty() { return Err("no editor specified".to_string()); }
+	let args: Vec<String> = command.split_off(1);
+
+	let status = Command::new(&command[0])
+		.args(args)
+		.arg(file)
+		.status()
+		.map_err(|e| format!("failed to execute editor \"{}\": {}", editor, e))?;
+	if !status.success() { return Err(format!("editor \"{}\" exited with {}, discarding changes", editor, status)); }
+	Ok(())
+}
+
+fn strip_errors(contents: &str) -> String {
+	contents
+		.lines()
+		.filter(|l| !l.starts_with(ERROR_PREFIX))
+		.map(|l| format!("{}\n", l))
+		.collect()
+}
+
+/// Parses and validates the edited document returning only the SchWiMarks which differ from the originals.
+fn parse_document(contents: &str, originals: &[EditedMark]) -> Result<Vec<EditedMark>, String> {
+	let document: EditDocument = toml::from_str(contents).map_err(|e| e.to_string())?;
+
+	let mut seen: Vec<i64> = vec![];
+	let mut errors: Vec<String> = vec![];
+	for mark in document.mark.iter() {
+		if !originals.iter().any(|o| o.id == mark.id) { errors.push(format!("unknown id {}, ids must not be changed", mark.id)); }
+		if seen.contains(&mark.id) { errors.push(format!("id {} appears more than once", mark.id)); }
+		if mark.name.trim().is_empty() { errors.push(format!("name of id {} cannot be empty", mark.id)); }
+		if mark.url.trim().is_empty() { errors.push(format!("url of id {} cannot be empty", mark.id)); }
+		if mark.tags.iter().any(|t| t.trim().is_empty()) { errors.push(format!("tags of id {} cannot be empty", mark.id)); }
+		let fields: [(&str, &str); 4] = [("name", &mark.name), ("description", &mark.description), ("url", &mark.url), ("application", &mark.application)];
+		for (field, value) in fields.into_iter().chain(mark.tags.iter().map(|t| ("tags", t.as_str()))) {
+			if value.contains(|c| sql::FORBIDDEN_CHARACTERS.contains(c)) {
+				errors.push(format!("{} of id {} cannot contain tabs, line breaks or null characters", field, mark.id));
+			}
+		}
+		seen.push(mark.id);
+	}
+	errors.dedup();
+	if !errors.is_empty() { return Err(errors.join("\n")); }
+
+	Ok(document.mark.into_iter().filter(|m| !originals.contains(m)).collect())
+}
+
+fn apply_changes(database: &Connection, originals: &[EditedMark], changes: &[EditedMark]) -> Result<usize, String> {
+	let transaction = database.unchecked_transaction().map_err(|e| e.to_string())?;
+
+	// renamed together so SchWiMarks can swap their names without clashing in the unique index
+	let renames: Vec<(i64, String)> = changes
+		.iter()
+		.filter(|c| originals.iter().any(|o| o.id == c.id && o.name != c.name))
+		.map(|c| (c.id, c.name.trim().to_string()))
+		.collect();
+	sql::rename_marks(&transaction, &renames).map_err(|e| format!("failed to rename SchWiMarks: {}", e))?;
+
+	for changed in changes {
+		let original: &EditedMark = originals.iter().find(|o| o.id == changed.id).expect("validated id");
+		apply_change(&transaction, original, changed)
+			.map_err(|e| format!("failed to update SchWiMark {}: {}", changed.id, e))?;
+	}
+
+	transaction.commit().map_err(|e| e.to_string())?;
+	Ok(changes.len())
+}
+
+/// Applies the changed fields except the name, which `apply_changes` renames beforehand.
+fn apply_change(database: &Connection, original: &EditedMark, changed: &EditedMark) -> rusqlite::Result<()> {
+	let id: i64 = changed.id;
+	if original.description != changed.description { sql::update_description(database, id, changed.description.clone())?; }
+	if original.url != changed.url { sql::update_url(database, id, changed.url.trim().to_string())?; }
+	if original.application != changed.application { sql::update_application(database, id, changed.application.clone())?; }
+
+	for tag in original.tags.iter().filter(|t| !changed.tags.contains(t)) {
+		sql::delete_tag(database, id, tag.clone())?;
+	}
+	let mut added: Vec<String> = vec![];
+	for tag in changed.tags.iter().filter(|t| !original.tags.contains(t)) {
+		if !added.contains(tag) { added.push(tag.clone()); }
+	}
+	sql::add_tags(database, id, added)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn original() -> EditedMark {
+		EditedMark {
+			id: 1,
+			name: "docs".to_string(),
+			description: "".to_string(),
+			url: "https://example.com".to_string(),
+			application: "".to_string(),
+			tags: vec!["work".to_string()],
+		}
+	}
+
+	fn database() -> (tempfile::TempDir, Connection) {
+		let directory: tempfile::TempDir = tempfile::tempdir().unwrap();
+		let mut config: crate::config::Config = crate::config::Config::default();
+		config.database_file = directory.path().join("schwimark.db");
+		let database: Connection = sql::create_database(&config.database_file, &crate::backup::Backups::new(&config)).unwrap();
+		(directory, database)
+	}
+
+	fn edited(database: &Connection, id: i64) -> EditedMark {
+		let mark: sql::SchWiMark = sql::get_mark(database, id).unwrap();
+		EditedMark {
+			id,
+			name: mark.name().to_string(),
+			description: mark.description().to_string(),
+			url: mark.url().to_string(),
+			application: mark.application().to_string(),
+			tags: sql::get_tags(database, id).unwrap(),
+		}
+	}
+
+	#[test]
+	fn apply_changes_swaps_names_and_updates_fields_and_tags() {
+		let (_directory, database): (tempfile::TempDir, Connection) = database();
+		let mut ids: Vec<i64> = vec![];
+		for (name, url) in [("docs", "https://docs.example.com"), ("wiki", "https://wiki.example.com")] {
+			let (_, tags) = sql::add_mark(&database, name.to_string(), "".to_string(), url.to_string(), "".to_string(), vec!["old".to_string()]).unwrap();
+			ids.push(tags.markid());
+		}
+		let originals: Vec<EditedMark> = ids.iter().map(|id| edited(&database, *id)).collect();
+
+		let mut first: EditedMark = edited(&database, ids[0]);
+		first.name = "wiki".to_string();
+		first.description = "the documentation".to_string();
+		first.tags = vec!["new".to_string()];
+		let mut second: EditedMark = edited(&database, ids[1]);
+		second.name = "docs".to_string();
+		assert_eq!(apply_changes(&database, &originals, &[first, second]).unwrap(), 2);
+
+		let first: EditedMark = edited(&database, ids[0]);
+		assert_eq!(first.name, "wiki");
+		assert_eq!(first.description, "the documentation");
+		assert_eq!(first.tags, ["new"]);
+		assert_eq!(edited(&database, ids[1]).name, "docs");
+	}
+
+	#[test]
+	fn apply_changes_rolls_back_when_a_name_is_taken() {
+		let (_directory, database): (tempfile::TempDir, Connection) = database();
+		let mut ids: Vec<i64> = vec![];
+		for name in ["docs", "wiki"] {
+			let (_, tags) = sql::add_mark(&database, name.to_string(), "".to_string(), "https://example.com".to_string(), "".to_string(), vec![]).unwrap();
+			ids.push(tags.markid());
+		}
+		let originals: Vec<EditedMark> = vec![edited(&database, ids[0])];
+		let mut changed: EditedMark = edited(&database, ids[0]);
+		changed.name = "wiki".to_string();
+		changed.description = "changed".to_string();
+
+		assert!(apply_changes(&database, &originals, &[changed]).is_err());
+		let unchanged: EditedMark = edited(&database, ids[0]);
+		assert_eq!(unchanged.name, "docs");
+		assert_eq!(unchanged.description, "");
+	}
+
+	#[test]
+	fn parse_document_returns_changed_marks() {
+		let document: &str = "[[mark]]\nid = 1\nname = \"renamed\"\ndescription = \"\"\nurl = \"https://example.com\"\napplication = \"\"\ntags = [\"work\"]\n";
+		let changes: Vec<EditedMark> = parse_document(document, &[original()]).unwrap();
+		assert_eq!(changes.len(), 1);
+		assert_eq!(changes[0].name, "renamed");
+	}
+
+	#[test]
+	fn parse_document_rejects_separator_characters() {
+		for (field, value) in [("name", "a\\tb"), ("description", "a\\nb"), ("url", "a\\u0000b"), ("application", "a\\tb")] {
+			let mut fields: Vec<(&str, &str)> = vec![("name", "docs"), ("description", ""), ("url", "https://example.com"), ("application", "")];
+			fields.iter_mut().filter(|(f, _)| *f == field).for_each(|(_, v)| *v = value);
+			let document: String = fields.iter().fold("[[mark]]\nid = 1\ntags = []\n".to_string(), |d, (f, v)| format!("{}{} = \"{}\"\n", d, f, v));
+			let error: String = parse_document(&document, &[original()]).err().unwrap();
+			assert!(error.contains(field), "{}", error);
+		}
+
+		let document: &str = "[[mark]]\nid = 1\nname = \"docs\"\ndescription = \"\"\nurl = \"u\"\napplication = \"\"\ntags = [\"a\\tb\"]\n";
+		assert!(parse_document(document, &[original()]).err().unwrap().contains("tags"));
+	}
+}
@@ -1,7 +1,22 @@
+mod archive;
+mod backup;
+mod check;
 mod cli;
+mod clipboard;
+mod completions;
 mod config;
 mod dmenu_handler;
+mod editor;
+mod manpage;
+mod merge;
+mod metadata;
+mod opener;
+mod paths;
+mod prompt;
 mod sql;
+mod stats;
+mod sync;
+mod tui;
 
 fn main() {
 	cli::start_cli();
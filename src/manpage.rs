@@ -0,0 +1,33 @@
+use std::path::Path;
+use clap::Command;
+use clap_mangen::Man;
+
+/// Sections clap does not know about, appended to the page.
+const EXTRA_SECTIONS: &str = "\
+.SH ENVIRONMENT
+Every setting of the config can be overridden with \\fBSCHWIMARK_\\fISETTING\\fR (e.g. \\fBSCHWIMARK_DMENU_COMMAND\\fR), \
+lists are separated by commas. \\fBSCHWIMARK_DATABASE\\fR and \\fBSCHWIMARK_MENU\\fR are short for the database file and the menu command.
+.SH FILES
+.TP
+\\fI$XDG_CONFIG_HOME/schwimark/config.toml\\fR
+the config file, \\fBschwimark config init\\fR writes a commented template
+.TP
+\\fI$XDG_DATA_HOME/schwimark/\\fR
+the database, its backups and the archived pages
+";
+
+/// Renders the man page (roff) of the program from the definition of the command line, so it
+/// documents the same commands and options as `--help`.
+pub fn render(command: Command) -> Result<String, String> {
+	let mut page: Vec<u8> = vec![];
+	Man::new(command).render(&mut page).map_err(|e| format!("failed to render the man page: {}", e))?;
+	page.extend_from_slice(EXTRA_SECTIONS.as_bytes());
+	String::from_utf8(page).map_err(|e| e.to_string())
+}
+
+/// Writes a man page for the program and one for every command (e.g. `schwimark-open.1`) into `directory`.
+pub fn write_pages(command: Command, directory: &Path) -> Result<(), String> {
+	std::fs::create_dir_all(directory).map_err(|e| format!("failed to create \"{}\": {}", directory.display(), e))?;
+	clap_mangen::generate_to(command, directory)
+		.map_err(|e| format!("failed to write the man pages to \"{}\": {}", directory.display(), e))
+}
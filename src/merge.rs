@@ -0,0 +1,169 @@
+use crate::sql;
+use crate::sync;
+
+use std::path::Path;
+use rusqlite::Connection;
+
+/// What a merge did, printed by the cli.
+#[derive(Default)]
+pub struct MergeReport {
+	pub added: usize,
+	pub updated: usize,
+	pub conflicts: usize,
+}
+
+/// Folds the SchWiMarks of another database into `database` in a single transaction.
+/// A SchWiMark matches a local one with the same uuid, name or canonical url. Matching SchWiMarks
+/// get the tags they are missing, empty local fields are filled in and differing fields are passed
+/// to the resolver. All other SchWiMarks are added.
+pub fn merge_database(database: &Connection, path: &Path, resolution: sync::Resolution) -> Result<MergeReport, String> {
+	if !path.is_file() { return Err(format!("\"{}\" is not a SchWiMark database", path.display())); }
+	let others: Vec<(sql::SchWiMark, sql::Tag)> = sql::get_foreign_marks(database, path)
+		.map_err(|e| format!("failed to read \"{}\": {}", path.display(), e))?;
+	let locals: Vec<(sql::SchWiMark, sql::Tag)> = sql::get_all_marks(database).map_err(|e| e.to_string())?;
+
+	let mut report: MergeReport = MergeReport::default();
+	let mut resolver: sync::Resolver = sync::Resolver::new(resolution);
+	let transaction = database.unchecked_transaction().map_err(|e| e.to_string())?;
+
+	for (other, other_tags) in others.iter() {
+		let (local, local_tags) = match find_match(&locals, other) {
+			Some(m) => { m }
+			None => {
+				let uuid_taken: bool = other.uuid().is_empty()
+					|| sql::find_mark_by_uuid(&transaction, other.uuid()).map_err(|e| e.to_string())?.is_some();
+				let uuid: String = if uuid_taken { sql::new_uuid() } else { other.uuid().to_string() };
+				sql::import_mark(&transaction, &uuid, other.name(), other.description(), other.url(), other.application(), other_tags.tags())
+					.map_err(|e| format!("failed to add SchWiMark \"{}\": {}", other.name(), e))?;
+				report.added += 1;
+				continue;
+			}
+		};
+
+		let updated: bool = merge_mark(&transaction, local, local_tags, other, other_tags, &mut resolver)
+			.map_err(|e| format!("failed to update SchWiMark \"{}\": {}", local.name(), e))?;
+		if updated { report.updated += 1; }
+	}
+
+	transaction.commit().map_err(|e| e.to_string())?;
+	report.conflicts = resolver.conflicts();
+	Ok(report)
+}
+
+fn find_match<'a>(locals: &'a [(sql::SchWiMark, sql::Tag)], other: &sql::SchWiMark) -> Option<&'a (sql::SchWiMark, sql::Tag)> {
+	let url: String = canonical_url(other.url());
+	locals.iter().find(|(l, _)| !other.uuid().is_empty() && l.uuid() == other.uuid())
+		.or(locals.iter().find(|(l, _)| l.name() == other.name()))
+		.or(locals.iter().find(|(l, _)| canonical_url(l.url()) == url))
+}
+
+/// Normalizes a url so trivially different spellings of the same url compare equal:
+/// the scheme and host are lowercased and trailing slashes are removed.
+fn canonical_url(url: &str) -> String {
+	let url: &str = url.trim().trim_end_matches('/');
+	match url.split_once("://") {
+		Some((scheme, rest)) => {
+			let (host, path): (&str, &str) = match rest.find('/') {
+				Some(i) => { rest.split_at(i) }
+				None => { (rest, "") }
+			};
+			format!("{}://{}{}", scheme.to_lowercase(), host.to_lowercase(), path)
+		}
+		None => { url.to_string() }
+	}
+}
+
+/// Applies the fields and tags of `other` to the matching local SchWiMark, returns whether anything changed.
+fn merge_mark(
+	database: &Connection,
+	local: &sql::SchWiMark,
+	local_tags: &sql::Tag,
+	other: &sql::SchWiMark,
+	other_tags: &sql::Tag,
+	resolver: &mut sync::Resolver,
+	) -> Result<bool, String> {
+
+	let id: i64 = local.id();
+	let mut updated: bool = false;
+
+	// another local SchWiMark may already have the name, renaming would clash in the unique index
+	let name_taken: bool = local.name() != other.name()
+		&& sql::find_mark_by_name(database, other.name()).map_err(|e| e.to_string())?.is_some_and(|i| i != id);
+	if name_taken {
+		resolver.keep_local_because(local.name(), "name", other.name(), "another SchWiMark already has this name");
+	}
+
+	let mut take_other = |what: &str, l: &str, o: &str| -> Result<bool, String> {
+		if l == o || o.is_empty() { return Ok(false); }
+		if l.is_empty() { return Ok(true); }
+		Ok(!resolver.keep_local(local.name(), what, l, o)?)
+	};
+
+	if !name_taken && take_other("name", local.name(), other.name())? {
+		sql::update_name(database, id, other.name().to_string()).map_err(|e| e.to_string())?;
+		updated = true;
+	}
+	if take_other("description", local.description(), other.description())? {
+		sql::update_description(database, id, other.description().to_string()).map_err(|e| e.to_string())?;
+		updated = true;
+	}
+	if canonical_url(local.url()) != canonical_url(other.url()) && take_other("url/path", local.url(), other.url())? {
+		sql::update_url(database, id, other.url().to_string()).map_err(|e| e.to_string())?;
+		updated = true;
+	}
+	if take_other("application", local.application(), other.application())? {
+		sql::update_application(database, id, other.application().to_string()).map_err(|e| e.to_string())?;
+		updated = true;
+	}
+
+	let missing_tags: Vec<String> = other_tags.tags().iter().filter(|t| !local_tags.tags().contains(t)).cloned().collect();
+	if !missing_tags.is_empty() {
+		sql::add_tags(database, id, missing_tags).map_err(|e| e.to_string())?;
+		updated = true;
+	}
+	Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::backup;
+	use crate::config;
+	use tempfile::TempDir;
+
+	fn database(directory: &Path, file: &str) -> Connection {
+		let mut config: config::Config = config::Config::default();
+		config.database_file = directory.join(file);
+		sql::create_database(&config.database_file, &backup::Backups::new(&config)).unwrap()
+	}
+
+	fn names(database: &Connection) -> Vec<String> {
+		let mut names: Vec<String> = sql::get_all_marks(database).unwrap().into_iter().map(|(m, _)| m.name().to_string()).collect();
+		names.sort();
+		names
+	}
+
+	#[test]
+	fn renaming_to_a_taken_name_is_a_conflict() {
+		let directory: TempDir = tempfile::tempdir().unwrap();
+		let local: Connection = database(directory.path(), "local.db");
+		let other: Connection = database(directory.path(), "other.db");
+		for (name, url) in [("docs", "https://docs.example.com"), ("wiki", "https://wiki.example.com")] {
+			sql::add_mark(&local, name.to_string(), "".to_string(), url.to_string(), "".to_string(), vec![]).unwrap();
+		}
+		// the other database renamed "docs" to "wiki" and "wiki" to "old wiki"
+		for (mark, _) in sql::get_all_marks(&local).unwrap() {
+			let name: &str = match mark.name() {
+				"docs" => { "wiki" }
+				_ => { "old wiki" }
+			};
+			sql::import_mark(&other, mark.uuid(), name, "", mark.url(), "", &[]).unwrap();
+		}
+		drop(other);
+
+		let report: MergeReport = merge_database(&local, &directory.path().join("other.db"), sync::Resolution::Remote).unwrap();
+		// renaming "wiki" is resolved with the remote side, renaming "docs" cannot be
+		assert_eq!(report.conflicts, 2);
+		assert_eq!(names(&local), ["docs", "old wiki"]);
+	}
+}
@@ -0,0 +1,212 @@
+use crate::check;
+
+/// Information about a web page used to fill in a new SchWiMark.
+#[derive(Default)]
+pub struct PageMetadata {
+	/// `og:title`, otherwise `<title>`
+	pub title: Option<String>,
+	/// `meta description`, otherwise `og:description`
+	pub description: Option<String>,
+	/// lowercase `meta keywords` and `article:tag`s, suggested as tags
+	pub keywords: Vec<String>,
+}
+
+/// Downloads the page at `url` and reads its metadata.
+pub fn fetch_metadata(client: &dyn check::HttpClient, url: &str) -> Result<PageMetadata, String> {
+	let followed: check::Followed = check::follow(client, check::Method::Get, url)?;
+	if followed.response.status >= 400 {
+		return Err(format!("the server responded with {}", followed.response.status));
+	}
+	Ok(parse_metadata(&String::from_utf8_lossy(&followed.response.body)))
+}
+
+fn parse_metadata(html: &str) -> PageMetadata {
+	let lowercase: String = html.to_ascii_lowercase();
+	let head: &str = match lowercase.find("<body") {
+		Some(i) => { &html[..i] }
+		None => { html }
+	};
+
+	let mut metadata: PageMetadata = PageMetadata::default();
+	let mut og_title: Option<String> = None;
+	let mut og_description: Option<String> = None;
+
+	let head_lowercase: &str = &lowercase[..head.len()];
+	let mut position: usize = 0;
+	while let Some(start) = head_lowercase[position..].find("<meta").map(|i| position + i) {
+		let end: usize = head[start..].find('>').map(|i| start + i).unwrap_or(head.len());
+		let attributes: Vec<(String, String)> = parse_attributes(&head[start + 5..end]);
+		position = end;
+
+		let attribute = |name: &str| -> Option<&str> {
+			attributes.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+		};
+		let content: String = match attribute("content") {
+			Some(c) => { clean_text(c) }
+			None => { continue; }
+		};
+		if content.is_empty() { continue; }
+
+		let key: String = attribute("property").or(attribute("name")).unwrap_or_default().to_ascii_lowercase();
+		match key.as_str() {
+			"og:title" => { og_title.get_or_insert(content); }
+			"og:description" => { og_description.get_or_insert(content); }
+			"description" => { metadata.description.get_or_insert(content); }
+			"keywords" | "article:tag" => {
+				for keyword in content.split(',').map(|k| k.trim().to_lowercase()).filter(|k| !k.is_empty()) {
+					if !metadata.keywords.contains(&keyword) { metadata.keywords.push(keyword); }
+				}
+			}
+			_ => {}
+		}
+	}
+
+	let title: Option<String> = head_lowercase.find("<title").and_then(|start| {
+		let content_start: usize = start + head[start..].find('>')? + 1;
+		let content_end: usize = content_start + head_lowercase[content_start..].find("</title")?;
+		Some(clean_text(&head[content_start..content_end])).filter(|t| !t.is_empty())
+	});
+
+	metadata.title = og_title.or(title);
+	metadata.description = metadata.description.or(og_description);
+	metadata
+}
+
+/// Splits `name="value" name='value' name=value` into lowercase names and decoded values.
+pub fn parse_attributes(tag: &str) -> Vec<(String, String)> {
+	let mut attributes: Vec<(String, String)> = vec![];
+	let mut chars = tag.trim_end_matches('/').chars().peekable();
+
+	loop {
+		while chars.next_if(|c| c.is_whitespace() || *c == '/').is_some() {}
+		let name: String = std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace() && *c != '=')).collect();
+		if name.is_empty() { break; }
+		while chars.next_if(|c| c.is_whitespace()).is_some() {}
+		if chars.next_if_eq(&'=').is_none() {
+			attributes.push((name.to_ascii_lowercase(), String::new()));
+			continue;
+		}
+		while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+		let value: String = match chars.next_if(|c| *c == '"' || *c == '\'') {
+			Some(quote) => {
+				let value: String = std::iter::from_fn(|| chars.next_if(|c| *c != quote)).collect();
+				chars.next();
+				value
+			}
+			None => { std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace())).collect() }
+		};
+		attributes.push((name.to_ascii_lowercase(), decode_entities(&value)));
+	}
+	attributes
+}
+
+/// Decodes the entities of a title or attribute and collapses whitespace, including newlines and tabs.
+fn clean_text(text: &str) -> String {
+	decode_entities(text).split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn decode_entities(text: &str) -> String {
+	let mut decoded: String = String::with_capacity(text.len());
+	let mut rest: &str = text;
+
+	while let Some(start) = rest.find('&') {
+		decoded.push_str(&rest[..start]);
+		rest = &rest[start..];
+		let entity: Option<(char, usize)> = rest[1..].find(';').filter(|end| *end <= 10).and_then(|end| {
+			let name: &str = &rest[1..end + 1];
+			let c: Option<char> = match name {
+				"amp" => { Some('&') }
+				"lt" => { Some('<') }
+				"gt" => { Some('>') }
+				"quot" => { Some('"') }
+				"apos" => { Some('\'') }
+				"nbsp" => { Some(' ') }
+				_ if name.starts_with("#x") || name.starts_with("#X") => {
+					u32::from_str_radix(&name[2..], 16).ok().and_then(char::from_u32)
+				}
+				_ if name.starts_with('#') => { name[1..].parse::<u32>().ok().and_then(char::from_u32) }
+				_ => { None }
+			};
+			c.map(|c| (c, end + 2))
+		});
+
+		match entity {
+			Some((c, length)) => {
+				decoded.push(c);
+				rest = &rest[length..];
+			}
+			None => {
+				decoded.push('&');
+				rest = &rest[1..];
+			}
+		}
+	}
+	decoded.push_str(rest);
+	decoded
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn og_title_is_preferred_over_the_title() {
+		let metadata: PageMetadata = parse_metadata(
+			"<html><head><title>Docs | Example</title><meta property=\"og:title\" content=\"Docs\"></head></html>"
+		);
+		assert_eq!(metadata.title.as_deref(), Some("Docs"));
+
+		let metadata: PageMetadata = parse_metadata("<head><TITLE>\n\tDocs  |\n Example </TITLE></head>");
+		assert_eq!(metadata.title.as_deref(), Some("Docs | Example"));
+	}
+
+	#[test]
+	fn meta_description_is_preferred_over_og_description() {
+		let metadata: PageMetadata = parse_metadata(
+			"<meta property=\"og:description\" content=\"from og\"><meta name=\"Description\" content=\"from meta\">"
+		);
+		assert_eq!(metadata.description.as_deref(), Some("from meta"));
+
+		let metadata: PageMetadata = parse_metadata("<meta property=\"og:description\" content=\"from og\"><meta name=\"description\" content=\"\">");
+		assert_eq!(metadata.description.as_deref(), Some("from og"));
+	}
+
+	#[test]
+	fn metadata_of_the_body_is_ignored() {
+		let metadata: PageMetadata = parse_metadata("<head></head><body><title>Not the title</title><meta name=\"description\" content=\"no\"></body>");
+		assert!(metadata.title.is_none());
+		assert!(metadata.description.is_none());
+	}
+
+	#[test]
+	fn keywords_and_article_tags_become_lowercase_unique_tags() {
+		let metadata: PageMetadata = parse_metadata(
+			"<meta name=\"keywords\" content=\"Rust, CLI,, bookmarks \"><meta property=\"article:tag\" content=\"rust\"><meta property=\"article:tag\" content=\"SQLite\">"
+		);
+		assert_eq!(metadata.keywords, ["rust", "cli", "bookmarks", "sqlite"]);
+	}
+
+	#[test]
+	fn parse_attributes_handles_quoting_and_order() {
+		assert_eq!(
+			parse_attributes(" CONTENT='it\"s' name = \"a b\" data-x=plain async/"),
+			[
+				("content".to_string(), "it\"s".to_string()),
+				("name".to_string(), "a b".to_string()),
+				("data-x".to_string(), "plain".to_string()),
+				("async".to_string(), "".to_string()),
+			],
+		);
+		let metadata: PageMetadata = parse_metadata("<meta content=\"Docs\" property=og:title />");
+		assert_eq!(metadata.title.as_deref(), Some("Docs"));
+	}
+
+	#[test]
+	fn decode_entities_decodes_named_and_numeric_entities() {
+		assert_eq!(decode_entities("Tom &amp; Jerry &lt;3 &quot;a&quot; &apos;b&apos;"), "Tom & Jerry <3 \"a\" 'b'");
+		assert_eq!(decode_entities("&#233;t&#xE9; &#X41;"), "été A");
+		assert_eq!(decode_entities("a & b &unknown; &amp"), "a & b &unknown; &amp");
+		assert_eq!(clean_text("a&nbsp;&nbsp;b\n\tc"), "a b c");
+	}
+}
@@ -0,0 +1,192 @@
+use std::fs::File;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use crate::config;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Launches applications for SchWiMarks detached from schwimark itself.
+/// The child is moved into its own session so it neither holds on to the terminal nor gets killed
+/// with it, and its output is either discarded or appended to the configured log file.
+pub struct Opener {
+	grace_period: Duration,
+	log_file: Option<PathBuf>,
+	/// opens the url with the default application
+	fallback: fn(&str) -> std::io::Result<()>,
+}
+
+impl Opener {
+	pub fn new(config: &config::Config) -> Opener {
+		Opener {
+			grace_period: Duration::from_millis(config.opener_grace_period_ms),
+			log_file: match config.opener_log_file.as_os_str().is_empty() {
+				true => { None }
+				false => { Some(config.opener_log_file.clone()) }
+			},
+			fallback: |url| open::that(url),
+		}
+	}
+
+	/// Opens `url` with the given command line, falling back to the default application via
+	/// `open::that` if the command could not be spawned or exited unsuccessfully within the
+	/// grace period. Returns the error of the default application if that failed as well.
+	pub fn open(&self, url: &str, command: Option<Vec<String>>) -> Result<(), String> {
+		if let Some(command) = command {
+			match self.spawn(&command) {
+				Ok(_) => { return Ok(()); }
+				Err(e) => {
+					eprintln!("{}\nattempting to open via default application", e);
+				}
+			}
+		}
+
+		(self.fallback)(url).map_err(|e| format!("failed to open \"{}\" with the default application: {}", url, e))
+	}
+
+	/// Spawns `command` detached and, if a grace period is configured, waits for it to detect
+	/// applications that exit with a non-zero status right away.
+	pub fn spawn(&self, command: &[String]) -> Result<(), String> {
+		if command.is_empty() { return Err("cannot spawn an empty command".to_string()); }
+		let command_line: String = shlex::try_join(command.iter().map(|s| s.as_str()))
+			.unwrap_or_else(|_| command.join(" "));
+
+		let (stdout, stderr): (Stdio, Stdio) = match &self.log_file {
+			Some(log_file) => {
+				let log: File = File::options()
+					.create(true)
+					.append(true)
+					.open(log_file)
+					.map_err(|e| format!("failed to open the log file \"{}\": {}", log_file.display(), e))?;
+				let log_err: File = log.try_clone().map_err(|e| e.to_string())?;
+				(Stdio::from(log), Stdio::from(log_err))
+			}
+			None => { (Stdio::null(), Stdio::null()) }
+		};
+
+		let mut process: Command = Command::new(&command[0]);
+		process
+			.args(&command[1..])
+			.stdin(Stdio::null())
+			.stdout(stdout)
+			.stderr(stderr);
+		// SAFETY: the closure runs between fork and exec, where only async-signal-safe functions may be
+		// called. It only calls setsid and reads errno on failure, neither of which allocates or locks.
+		unsafe {
+			process.pre_exec(|| {
+				if libc::setsid() == -1 { return Err(std::io::Error::last_os_error()); }
+				Ok(())
+			});
+		}
+
+		let child: Child = process
+			.spawn()
+			.map_err(|e| format!("failed to execute \"{}\": {}", command_line, e))?;
+
+		self.wait_grace_period(child, &command_line)
+	}
+
+	fn wait_grace_period(&self, mut child: Child, command_line: &str) -> Result<(), String> {
+		let start: Instant = Instant::now();
+		while start.elapsed() < self.grace_period {
+			match child.try_wait() {
+				Ok(Some(status)) if status.success() => { return Ok(()); }
+				Ok(Some(status)) => { return Err(format!("\"{}\" exited with {}", command_line, status)); }
+				Ok(None) => { sleep(POLL_INTERVAL); }
+				Err(e) => { return Err(format!("failed to wait for \"{}\": {}", command_line, e)); }
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::cell::RefCell;
+	use std::fs;
+
+	thread_local! {
+		static FALLBACK_URLS: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+	}
+
+	fn record_fallback(url: &str) -> std::io::Result<()> {
+		FALLBACK_URLS.with(|u| u.borrow_mut().push(url.to_string()));
+		Ok(())
+	}
+
+	fn failing_fallback(_: &str) -> std::io::Result<()> {
+		Err(std::io::Error::other("no default application"))
+	}
+
+	fn opener(grace_period_ms: u64) -> Opener {
+		Opener { grace_period: Duration::from_millis(grace_period_ms), log_file: None, fallback: record_fallback }
+	}
+
+	/// A fake application: a shell script run by `sh -c` with the url as its argument.
+	fn fake_app(script: &str) -> Vec<String> {
+		["sh", "-c", script, "fake app", "https://example.com/a b"].map(String::from).to_vec()
+	}
+
+	fn fallback_urls() -> Vec<String> {
+		FALLBACK_URLS.with(|u| u.borrow_mut().drain(..).collect())
+	}
+
+	#[test]
+	fn successful_application_does_not_fall_back() {
+		assert_eq!(opener(1000).open("https://example.com", Some(fake_app("exit 0"))), Ok(()));
+		assert!(fallback_urls().is_empty());
+	}
+
+	#[test]
+	fn application_running_past_the_grace_period_is_a_success() {
+		assert_eq!(opener(50).open("https://example.com", Some(fake_app("sleep 1; exit 1"))), Ok(()));
+		assert!(fallback_urls().is_empty());
+	}
+
+	#[test]
+	fn early_exit_reports_the_command_line_and_falls_back() {
+		let error: String = opener(2000).spawn(&fake_app("exit 3")).unwrap_err();
+		assert!(error.contains("sh -c 'exit 3' 'fake app' 'https://example.com/a b'"), "{}", error);
+		assert!(error.contains("exit status: 3"), "{}", error);
+
+		assert_eq!(opener(2000).open("https://example.com", Some(fake_app("exit 3"))), Ok(()));
+		assert_eq!(fallback_urls(), ["https://example.com"]);
+	}
+
+	#[test]
+	fn spawn_failure_reports_the_command_line_and_falls_back() {
+		let command: Vec<String> = ["/nonexistent/fake-app", "--new-window", "https://example.com"].map(String::from).to_vec();
+		let error: String = opener(0).spawn(&command).unwrap_err();
+		assert!(error.contains("\"/nonexistent/fake-app --new-window https://example.com\""), "{}", error);
+
+		assert_eq!(opener(0).open("https://example.com", Some(command)), Ok(()));
+		assert_eq!(fallback_urls(), ["https://example.com"]);
+	}
+
+	#[test]
+	fn no_application_uses_the_default_application() {
+		assert_eq!(opener(0).open("https://example.com", None), Ok(()));
+		assert_eq!(fallback_urls(), ["https://example.com"]);
+	}
+
+	#[test]
+	fn failing_default_application_is_reported() {
+		let opener: Opener = Opener { fallback: failing_fallback, ..opener(0) };
+		let error: String = opener.open("https://example.com", Some(vec!["/nonexistent/fake-app".to_string()])).unwrap_err();
+		assert!(error.contains("failed to open \"https://example.com\" with the default application: no default application"), "{}", error);
+	}
+
+	#[test]
+	fn output_is_appended_to_the_log_file() {
+		let directory: tempfile::TempDir = tempfile::tempdir().unwrap();
+		let log_file: PathBuf = directory.path().join("opener.log");
+		fs::write(&log_file, "before\n").unwrap();
+		let opener: Opener = Opener { log_file: Some(log_file.clone()), ..opener(2000) };
+
+		assert_eq!(opener.spawn(&fake_app("echo \"$1\"; echo error >&2")), Ok(()));
+		assert_eq!(fs::read_to_string(&log_file).unwrap(), "before\nhttps://example.com/a b\nerror\n");
+	}
+}
@@ -0,0 +1,146 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Whether the url/path of a SchWiMark refers to a local file: an absolute path, a path relative to
+/// the current (`./`) or parent (`../`) directory, a path starting with `~` or an environment variable,
+/// or a file: url. Everything else (https://, mailto:, `example.com`, ...) is not.
+pub fn is_local(url: &str) -> bool {
+	let is_file_url: bool = url.split_once(':').is_some_and(|(scheme, _)| scheme.eq_ignore_ascii_case("file"));
+	is_file_url
+		|| url.starts_with(['/', '~', '$'])
+		|| ["./", "../"].iter().any(|p| url.starts_with(p))
+		|| url == "."
+		|| url == ".."
+}
+
+/// Turns the url/path of a local SchWiMark into the path to open: file: urls are turned into their
+/// path, `~` and environment variables (`$VAR` or `${VAR}`) are expanded and relative paths are resolved
+/// against `base_directory` (which may use `~` and environment variables as well).
+pub fn resolve(url: &str, base_directory: &Path) -> PathBuf {
+	let path: PathBuf = match file_url_path(url) {
+		Some(p) => { expand(&p) }
+		None => { expand(url) }
+	};
+	if path.is_relative() && !base_directory.as_os_str().is_empty() {
+		return expand(&base_directory.to_string_lossy()).join(path);
+	}
+	path
+}
+
+/// Path of a file: url (`file:///x`, `file://localhost/x`, `file:/x` or the relative `file:x`) with
+/// the query and fragment removed and percent-escapes decoded, None for anything else.
+fn file_url_path(url: &str) -> Option<String> {
+	let (scheme, rest) = url.split_once(':')?;
+	if !scheme.eq_ignore_ascii_case("file") { return None; }
+	let rest: &str = rest.split(['?', '#']).next().unwrap_or_default();
+	let path: &str = match rest.strip_prefix("//") {
+		Some(authority_and_path) => {
+			let (authority, path) = authority_and_path.split_at(authority_and_path.find('/').unwrap_or(authority_and_path.len()));
+			match authority.is_empty() || authority.eq_ignore_ascii_case("localhost") {
+				true => { path }
+				// a file on another host, which can only be reached as a network path
+				false => { rest }
+			}
+		}
+		None => { rest }
+	};
+	Some(percent_decode(path))
+}
+
+fn percent_decode(text: &str) -> String {
+	let bytes: &[u8] = text.as_bytes();
+	let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+	let mut i: usize = 0;
+	while i < bytes.len() {
+		let escaped: Option<u8> = match bytes[i] {
+			b'%' => { text.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) }
+			_ => { None }
+		};
+		match escaped {
+			Some(b) => {
+				decoded.push(b);
+				i += 3;
+			}
+			None => {
+				decoded.push(bytes[i]);
+				i += 1;
+			}
+		}
+	}
+	String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn expand(path: &str) -> PathBuf {
+	let path: String = expand_variables(path);
+	match (path.strip_prefix('~'), env::var("HOME")) {
+		(Some(rest), Ok(home)) if rest.is_empty() || rest.starts_with('/') => { PathBuf::from(home + rest) }
+		_ => { PathBuf::from(path) }
+	}
+}
+
+/// Replaces `$VAR` and `${VAR}` with the value of the environment variable, unset variables are left as they are.
+fn expand_variables(path: &str) -> String {
+	let mut expanded: String = String::with_capacity(path.len());
+	let mut rest: &str = path;
+
+	while let Some(start) = rest.find('$') {
+		expanded.push_str(&rest[..start]);
+		rest = &rest[start + 1..];
+		let (name, length): (&str, usize) = match rest.strip_prefix('{') {
+			Some(braced) => {
+				match braced.find('}') {
+					Some(end) => { (&braced[..end], end + 2) }
+					None => { ("", 0) }
+				}
+			}
+			None => {
+				let end: usize = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+				(&rest[..end], end)
+			}
+		};
+
+		match env::var(name) {
+			Ok(value) if !name.is_empty() => { expanded.push_str(&value); }
+			_ => {
+				expanded.push('$');
+				expanded.push_str(&rest[..length]);
+			}
+		}
+		rest = &rest[length..];
+	}
+	expanded.push_str(rest);
+	expanded
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn only_paths_and_file_urls_are_local() {
+		for local in ["/home/user/notes.md", "./notes.md", "../notes.md", ".", "..", "~", "~/notes.md", "$HOME/notes.md", "${XDG_DATA_HOME}/x", "file:///tmp/x", "FILE:///tmp/x"] {
+			assert!(is_local(local), "{} is local", local);
+		}
+		for remote in ["https://example.com", "mailto:someone@example.com", "example.com", "example.com/path", "notes.md", ".hidden", "localhost:8080"] {
+			assert!(!is_local(remote), "{} is not local", remote);
+		}
+	}
+
+	#[test]
+	fn relative_paths_are_resolved_against_the_base_directory() {
+		assert_eq!(resolve("./notes.md", Path::new("/srv")), PathBuf::from("/srv/./notes.md"));
+		assert_eq!(resolve("file:///tmp/x", Path::new("/srv")), PathBuf::from("/tmp/x"));
+		assert_eq!(resolve("../notes.md", Path::new("")), PathBuf::from("../notes.md"));
+	}
+
+	#[test]
+	fn file_urls_are_resolved_to_their_path() {
+		assert_eq!(resolve("file:///home/user/My%20Notes/caf%C3%A9.md", Path::new("/srv")), PathBuf::from("/home/user/My Notes/café.md"));
+		assert_eq!(resolve("FILE://localhost/tmp/x?query#top", Path::new("/srv")), PathBuf::from("/tmp/x"));
+		assert_eq!(resolve("file:/tmp/100%", Path::new("/srv")), PathBuf::from("/tmp/100%"));
+		assert_eq!(resolve("file:notes/todo.md", Path::new("/srv")), PathBuf::from("/srv/notes/todo.md"));
+		assert_eq!(resolve("file://server/share/x", Path::new("")), PathBuf::from("//server/share/x"));
+		// only file: urls are decoded
+		assert_eq!(resolve("/tmp/a%20b", Path::new("")), PathBuf::from("/tmp/a%20b"));
+	}
+}
@@ -0,0 +1,124 @@
+use crate::sql;
+
+use std::path::PathBuf;
+use std::process::exit;
+use rusqlite::Connection;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::FileHistory;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Context, Editor, Helper};
+
+const HISTORY_FILE_NAME: &str = "history";
+
+/// What the input of a prompt is completed from when pressing tab.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Completion {
+	None,
+	Tags,
+	Applications,
+}
+
+struct PromptHelper {
+	completion: Completion,
+	tags: Vec<String>,
+	applications: Vec<String>,
+}
+
+impl Completer for PromptHelper {
+	type Candidate = String;
+
+	fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+		let candidates: &Vec<String> = match self.completion {
+			Completion::None => { return Ok((0, vec![])); }
+			Completion::Tags => { &self.tags }
+			Completion::Applications => { &self.applications }
+		};
+		let prefix: &str = &line[..pos];
+		Ok((0, candidates.iter().filter(|c| c.starts_with(prefix)).cloned().collect()))
+	}
+}
+
+impl Hinter for PromptHelper {
+	type Hint = String;
+}
+
+impl Highlighter for PromptHelper {}
+
+impl Validator for PromptHelper {}
+
+impl Helper for PromptHelper {}
+
+/// Line editing prompt used by the interactive cli. Supports cursor movement, a history shared
+/// between sessions, prefilled values and tab completion of tags and applications from the database.
+pub struct Prompt {
+	editor: Editor<PromptHelper, FileHistory>,
+	history_file: Option<PathBuf>,
+}
+
+impl Prompt {
+	pub fn new(database: &Connection) -> Prompt {
+		let config: rustyline::Config = rustyline::Config::builder()
+			.completion_type(CompletionType::List)
+			.auto_add_history(true)
+			.build();
+		let mut editor: Editor<PromptHelper, FileHistory> = Editor::with_config(config)
+			.expect("failed to initialize the prompt");
+		editor.set_helper(Some(PromptHelper {
+			completion: Completion::None,
+			tags: sql::get_all_tags(database).expect("failed to query tags"),
+			applications: sql::get_all_applications(database).expect("failed to query applications"),
+		}));
+
+		let history_file: Option<PathBuf> = xdg::BaseDirectories::with_prefix("schwimark")
+			.place_data_file(HISTORY_FILE_NAME)
+			.ok();
+		if let Some(history_file) = &history_file {
+			let _ = editor.load_history(history_file);
+		}
+
+		Prompt {
+			editor,
+			history_file,
+		}
+	}
+
+	/// Reads a line with `initial` already filled in. Returns None on end of input (Ctrl-D),
+	/// an interrupt (Ctrl-C) exits.
+	pub fn read_line(&mut self, prompt: &str, initial: &str, completion: Completion) -> Option<String> {
+		if let Some(helper) = self.editor.helper_mut() { helper.completion = completion; }
+
+		match self.editor.readline_with_initial(prompt, (initial, "")) {
+			Ok(line) => { Some(line) }
+			Err(ReadlineError::Eof) => { None }
+			Err(ReadlineError::Interrupted) => {
+				self.save_history();
+				exit(130)
+			}
+			Err(e) => { panic!("Could not read input: {}", e) }
+		}
+	}
+
+	/// Offers `tags` in the tab completion of tags in addition to the tags of the database.
+	pub fn suggest_tags(&mut self, tags: &[String]) {
+		if let Some(helper) = self.editor.helper_mut() {
+			for tag in tags {
+				if !helper.tags.contains(tag) { helper.tags.push(tag.clone()); }
+			}
+		}
+	}
+
+	fn save_history(&mut self) {
+		if let Some(history_file) = &self.history_file {
+			let _ = self.editor.save_history(history_file);
+		}
+	}
+}
+
+impl Drop for Prompt {
+	fn drop(&mut self) {
+		self.save_history();
+	}
+}
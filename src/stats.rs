@@ -0,0 +1,102 @@
+use crate::sql;
+
+use std::fmt::Write;
+
+/// Renders the statistics as tables, one section after another.
+pub fn table(stats: &sql::Stats) -> String {
+	let mut out: String = String::new();
+	let _ = writeln!(out, "SchWiMarks: {}", stats.total);
+	let _ = writeln!(out, "database size: {} ({} bytes)", human_size(stats.database_size), stats.database_size);
+
+	let _ = writeln!(out, "\ntags ({})", stats.tags.len());
+	let _ = writeln!(out, "{0: <30} | {1: <0}", "tag", "SchWiMarks");
+	for tag in &stats.tags {
+		let _ = writeln!(out, "{0: <30} | {1: <0}", tag.tag, tag.count);
+	}
+
+	let _ = writeln!(out, "\ntags per SchWiMark");
+	let _ = writeln!(out, "{0: <30} | {1: <0}", "tags", "SchWiMarks");
+	for distribution in &stats.tag_distribution {
+		let _ = writeln!(out, "{0: <30} | {1: <0}", distribution.tags, distribution.count);
+	}
+
+	push_names(&mut out, "without tags", &stats.untagged);
+	push_names(&mut out, "without description", &stats.without_description);
+
+	let _ = writeln!(out, "\nduplicate urls ({})", stats.duplicate_urls.len());
+	let _ = writeln!(out, "{0: <50} | {1: <0}", "url/path", "SchWiMarks");
+	for duplicate in &stats.duplicate_urls {
+		let _ = writeln!(out, "{0: <50} | {1: <0}", duplicate.url, duplicate.names.join(", "));
+	}
+
+	push_opened(&mut out, "most opened", &stats.most_opened);
+	push_opened(&mut out, "least opened", &stats.least_opened);
+
+	let _ = writeln!(out, "\ntop domains");
+	let _ = writeln!(out, "{0: <50} | {1: <0}", "domain", "SchWiMarks");
+	for domain in &stats.domains {
+		let _ = writeln!(out, "{0: <50} | {1: <0}", domain.domain, domain.count);
+	}
+	out
+}
+
+fn push_names(out: &mut String, title: &str, names: &[String]) {
+	let _ = writeln!(out, "\n{} ({})", title, names.len());
+	for name in names {
+		let _ = writeln!(out, "{}", name);
+	}
+}
+
+fn push_opened(out: &mut String, title: &str, opened: &[sql::Opened]) {
+	let _ = writeln!(out, "\n{}", title);
+	let _ = writeln!(out, "{0: <30} | {1: <6} | {2: <0}", "name", "opened", "last opened");
+	for mark in opened {
+		let _ = writeln!(out, "{0: <30} | {1: <6} | {2: <0}", mark.name, mark.count, mark.last_opened.as_deref().unwrap_or("never"));
+	}
+}
+
+fn human_size(bytes: u64) -> String {
+	let units: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+	let mut size: f64 = bytes as f64;
+	let mut unit: usize = 0;
+	while size >= 1024.0 && unit < units.len() - 1 {
+		size /= 1024.0;
+		unit += 1;
+	}
+	match unit {
+		0 => { format!("{} {}", bytes, units[0]) }
+		_ => { format!("{:.1} {}", size, units[unit]) }
+	}
+}
+
+/// Renders the statistics as a JSON object.
+pub fn json(stats: &sql::Stats) -> Result<String, String> {
+	serde_json::to_string_pretty(stats).map(|j| j + "\n").map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn json_is_valid_and_escaped() {
+		let stats: sql::Stats = sql::Stats {
+			total: 1,
+			database_size: 4096,
+			tags: vec![sql::TagCount { tag: "a \"quoted\" tag".to_string(), count: 1 }],
+			tag_distribution: vec![sql::TagDistribution { tags: 1, count: 1 }],
+			untagged: vec![],
+			without_description: vec!["back\\slash".to_string()],
+			duplicate_urls: vec![],
+			most_opened: vec![sql::Opened { name: "docs".to_string(), count: 2, last_opened: None }],
+			least_opened: vec![],
+			domains: vec![sql::DomainCount { domain: "example.com".to_string(), count: 1 }],
+		};
+		let json: serde_json::Value = serde_json::from_str(&json(&stats).unwrap()).unwrap();
+		assert_eq!(json["database_size"], 4096);
+		assert_eq!(json["tags"][0]["tag"], "a \"quoted\" tag");
+		assert_eq!(json["without_description"][0], "back\\slash");
+		assert_eq!(json["most_opened"][0]["last_opened"], serde_json::Value::Null);
+		assert_eq!(json["domains"][0]["domain"], "example.com");
+	}
+}
@@ -0,0 +1,621 @@
+use crate::backup;
+use crate::config;
+use crate::sql;
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use rusqlite::Connection;
+use serde::{Serialize, Deserialize};
+
+const SYNC_FILE_NAME: &str = "schwimark.toml";
+const SYNC_REMOTE_NAME: &str = "origin";
+const DOCUMENT_HEADER: &str = "\
+# SchWiMarks exported by `schwimark sync`, do not edit by hand.
+";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+struct SyncedMark {
+	uuid: String,
+	name: String,
+	description: String,
+	url: String,
+	application: String,
+	tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct SyncDocument {
+	#[serde(default)]
+	mark: Vec<SyncedMark>,
+}
+
+/// How conflicting changes made on both sides are resolved.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Resolution {
+	Ask,
+	Local,
+	Remote,
+}
+
+/// What a sync did, printed by the cli.
+#[derive(Default)]
+pub struct SyncReport {
+	pub committed: bool,
+	pub merged: bool,
+	pub pushed: bool,
+	pub conflicts: usize,
+	pub imported: usize,
+}
+
+/// Synchronizes the database through a git working tree. The SchWiMarks are exported to a
+/// deterministic TOML file, committed and merged with the upstream branch three-way per SchWiMark
+/// (identified by its uuid), then the merged result is imported into the database and pushed.
+pub struct Sync {
+	directory: PathBuf,
+	remote: Option<String>,
+}
+
+impl Sync {
+	pub fn new(config: &config::Config) -> Sync {
+		let directory: PathBuf = match config.sync_directory.as_os_str().is_empty() {
+			true => {
+				config.database_file
+					.parent()
+					.map(|p| p.join("sync"))
+					.unwrap_or(PathBuf::from("sync"))
+			}
+			false => { config.sync_directory.clone() }
+		};
+
+		Sync {
+			directory,
+			remote: Some(config.sync_remote.clone()).filter(|r| !r.is_empty()),
+		}
+	}
+
+	pub fn run(&self, database: &Connection, backups: &backup::Backups, resolution: Resolution) -> Result<SyncReport, String> {
+		let mut report: SyncReport = SyncReport::default();
+		let mut resolver: Resolver = Resolver::new(resolution);
+		let cloned: bool = self.prepare_repository()?;
+
+		let mut local: SyncDocument = export(database)?;
+		if cloned {
+			// the database was never synced before, so it is combined with the cloned SchWiMarks
+			let cloned_document: SyncDocument = self.read_document("HEAD")?;
+			let merged: SyncDocument = merge(&SyncDocument::default(), &local, &cloned_document, &mut resolver)?;
+			report.imported += import_changes(database, backups, &local, &merged)?;
+			local = merged;
+		}
+		self.write_document(&local)?;
+		report.committed = self.commit("Update SchWiMarks")?;
+
+		if self.git(&["remote"])?.is_empty() {
+			report.conflicts = resolver.conflicts();
+			return Ok(report);
+		}
+		self.git(&["fetch", SYNC_REMOTE_NAME])?;
+
+		let upstream: String = match self.upstream()? {
+			Some(u) => { u }
+			None => {
+				// the remote branch does not exist yet
+				self.git(&["push", "--set-upstream", SYNC_REMOTE_NAME, "HEAD"])?;
+				report.pushed = true;
+				report.conflicts = resolver.conflicts();
+				return Ok(report);
+			}
+		};
+
+		// the database is only changed before git, so a failed merge never loses the merged SchWiMarks
+		if self.git_succeeds(&["merge-base", "--is-ancestor", &upstream, "HEAD"])? {
+			// nothing new upstream
+		} else if self.git_succeeds(&["merge-base", "--is-ancestor", "HEAD", &upstream])? {
+			let remote_document: SyncDocument = self.read_document(&upstream)?;
+			report.imported += import_changes(database, backups, &local, &remote_document)?;
+			self.git(&["merge", "--quiet", "--ff-only", &upstream])?;
+			report.merged = true;
+		} else {
+			let base: Option<String> = self.git(&["merge-base", "HEAD", &upstream]).ok();
+			let base_document: SyncDocument = match &base {
+				Some(b) => { self.read_document(b)? }
+				None => { SyncDocument::default() }
+			};
+			let remote_document: SyncDocument = self.read_document(&upstream)?;
+			let merged: SyncDocument = merge(&base_document, &local, &remote_document, &mut resolver)?;
+			report.imported += import_changes(database, backups, &local, &merged)?;
+
+			let mut merge_args: Vec<&str> = vec!["merge", "--quiet", "--no-ff", "--no-commit", "--strategy", "ours"];
+			if base.is_none() { merge_args.push("--allow-unrelated-histories"); }
+			merge_args.push(&upstream);
+			self.git(&merge_args)?;
+			self.write_document(&merged)?;
+			self.commit(&format!("Merge SchWiMarks from {}", upstream))?;
+			report.merged = true;
+		}
+
+		if !self.git_succeeds(&["merge-base", "--is-ancestor", "HEAD", &upstream])? {
+			self.git(&["push", "--quiet", SYNC_REMOTE_NAME, "HEAD"])?;
+			report.pushed = true;
+		}
+		report.conflicts = resolver.conflicts();
+		Ok(report)
+	}
+
+	/// Clones `sync_remote` into the sync directory or initializes an empty repository if there is none yet.
+	/// Returns whether the repository was cloned.
+	fn prepare_repository(&self) -> Result<bool, String> {
+		if self.directory.join(".git").exists() { return Ok(false); }
+		fs::create_dir_all(&self.directory)
+			.map_err(|e| format!("failed to create the sync directory \"{}\": {}", self.directory.display(), e))?;
+
+		match &self.remote {
+			Some(remote) => { self.git(&["clone", "--quiet", remote, "."])?; Ok(true) }
+			None => { self.git(&["init", "--quiet"])?; Ok(false) }
+		}
+	}
+
+	fn upstream(&self) -> Result<Option<String>, String> {
+		if let Ok(upstream) = self.git(&["rev-parse", "--abbrev-ref", "--symbolic-full-name", "@{upstream}"]) {
+			return Ok(Some(upstream));
+		}
+
+		let branch: String = self.git(&["symbolic-ref", "--short", "HEAD"])?;
+		let remote_branch: String = format!("{}/{}", SYNC_REMOTE_NAME, branch);
+		if !self.git_succeeds(&["rev-parse", "--verify", "--quiet", &format!("refs/remotes/{}", remote_branch)])? {
+			return Ok(None);
+		}
+		self.git(&["branch", "--set-upstream-to", &remote_branch])?;
+		Ok(Some(remote_branch))
+	}
+
+	/// Commits the sync file if it changed, returns whether a commit was made.
+	fn commit(&self, message: &str) -> Result<bool, String> {
+		self.git(&["add", SYNC_FILE_NAME])?;
+		let merging: bool = self.git_succeeds(&["rev-parse", "--verify", "--quiet", "MERGE_HEAD"])?;
+		let has_head: bool = self.git_succeeds(&["rev-parse", "--verify", "--quiet", "HEAD"])?;
+		if !merging && has_head && self.git_succeeds(&["diff", "--cached", "--quiet"])? { return Ok(false); }
+		self.git(&["commit", "--quiet", "--message", message])?;
+		Ok(true)
+	}
+
+	fn write_document(&self, document: &SyncDocument) -> Result<(), String> {
+		let rendered: String = toml::to_string(document).map_err(|e| e.to_string())?;
+		let path: PathBuf = self.directory.join(SYNC_FILE_NAME);
+		let mut file: fs::File = fs::File::create(&path)
+			.map_err(|e| format!("failed to write \"{}\": {}", path.display(), e))?;
+		file.write_all(format!("{}\n{}", DOCUMENT_HEADER, rendered).as_bytes())
+			.map_err(|e| format!("failed to write \"{}\": {}", path.display(), e))
+	}
+
+	/// Reads the sync file as of the given commit, a commit without it has no SchWiMarks.
+	fn read_document(&self, revision: &str) -> Result<SyncDocument, String> {
+		let object: String = format!("{}:{}", revision, SYNC_FILE_NAME);
+		if !self.git_succeeds(&["cat-file", "-e", &object])? { return Ok(SyncDocument::default()); }
+		let contents: String = self.git(&["show", &object])?;
+		toml::from_str(&contents).map_err(|e| format!("failed to parse {}: {}", object, e))
+	}
+
+	fn git_command(&self, args: &[&str]) -> Command {
+		let mut command: Command = Command::new("git");
+		command.arg("-C").arg(&self.directory).args(args);
+		command
+	}
+
+	/// Runs git in the sync directory and returns its trimmed output, or its error output if it fails.
+	fn git(&self, args: &[&str]) -> Result<String, String> {
+		let output = self.git_command(args)
+			.output()
+			.map_err(|e| format!("failed to execute git: {}", e))?;
+		if !output.status.success() {
+			return Err(format!(
+				"git {} failed: {}",
+				args.join(" "),
+				String::from_utf8_lossy(&output.stderr).trim(),
+			));
+		}
+		Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+	}
+
+	fn git_succeeds(&self, args: &[&str]) -> Result<bool, String> {
+		let output = self.git_command(args)
+			.output()
+			.map_err(|e| format!("failed to execute git: {}", e))?;
+		Ok(output.status.success())
+	}
+}
+
+/// Exports all SchWiMarks which are not in the trash ordered by uuid with sorted tags.
+fn export(database: &Connection) -> Result<SyncDocument, String> {
+	let mut marks: Vec<SyncedMark> = sql::get_all_marks(database)
+		.map_err(|e| e.to_string())?
+		.into_iter()
+		.map(|(mark, tags)| SyncedMark {
+			uuid: mark.uuid().to_string(),
+			name: mark.name().to_string(),
+			description: mark.description().to_string(),
+			url: mark.url().to_string(),
+			application: mark.application().to_string(),
+			tags: tags.tags().iter().cloned().collect::<BTreeSet<String>>().into_iter().collect(),
+		})
+		.collect();
+	marks.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+	Ok(SyncDocument { mark: marks })
+}
+
+/// Decides conflicting changes according to the resolution, asking on the terminal with `Resolution::Ask`.
+pub struct Resolver {
+	resolution: Resolution,
+	conflicts: usize,
+}
+
+impl Resolver {
+	pub fn new(resolution: Resolution) -> Resolver {
+		Resolver {
+			resolution,
+			conflicts: 0,
+		}
+	}
+
+	pub fn conflicts(&self) -> usize {
+		self.conflicts
+	}
+
+	/// Counts a conflict which cannot be decided either way, the local side is kept.
+	pub fn keep_local_because(&mut self, mark: &str, what: &str, remote: &str, reason: &str) {
+		self.conflicts += 1;
+		println!("conflict in SchWiMark \"{}\": {}\n  remote: {}\n  {}, keeping the local side", mark, what, remote, reason);
+	}
+
+	/// Decides a conflict, returns true if the local side is kept.
+	pub fn keep_local(&mut self, mark: &str, what: &str, local: &str, remote: &str) -> Result<bool, String> {
+		self.conflicts += 1;
+		println!("conflict in SchWiMark \"{}\": {}\n  local:  {}\n  remote: {}", mark, what, local, remote);
+		match self.resolution {
+			Resolution::Local => { return Ok(true); }
+			Resolution::Remote => { return Ok(false); }
+			Resolution::Ask => {}
+		}
+
+		let mut input_buf: String = String::with_capacity(2);
+		loop {
+			input_buf.clear();
+			eprint!("keep [l]ocal or [r]emote? ");
+			let read: usize = std::io::stdin().read_line(&mut input_buf).map_err(|e| e.to_string())?;
+			if read == 0 { return Err(format!("unresolved conflict in SchWiMark \"{}\", nothing was changed", mark)); }
+			match input_buf.trim() {
+				"l" | "L" => { return Ok(true); }
+				"r" | "R" => { return Ok(false); }
+				_ => { continue; }
+			}
+		}
+	}
+}
+
+/// Merges the local and remote SchWiMarks three-way against their common base.
+/// A change on only one side is taken, conflicting changes of the same field are passed to the resolver.
+fn merge(base: &SyncDocument, local: &SyncDocument, remote: &SyncDocument, resolver: &mut Resolver) -> Result<SyncDocument, String> {
+	let find = |document: &SyncDocument, uuid: &str| -> Option<SyncedMark> {
+		document.mark.iter().find(|m| m.uuid == uuid).cloned()
+	};
+	let uuids: BTreeSet<String> = local.mark.iter()
+		.chain(remote.mark.iter())
+		.map(|m| m.uuid.clone())
+		.collect();
+
+	let mut merged: Vec<SyncedMark> = vec![];
+	for uuid in uuids {
+		let (b, l, r) = (find(base, &uuid), find(local, &uuid), find(remote, &uuid));
+		let result: Option<SyncedMark> = match (&l, &r) {
+			_ if l == r => { l }
+			_ if l == b => { r }
+			_ if r == b => { l }
+			(Some(l), Some(r)) => { Some(merge_mark(b.as_ref(), l, r, resolver)?) }
+			(Some(m), None) | (None, Some(m)) => {
+				let keep_local: bool = resolver.keep_local(&m.name, "deleted on one side and changed on the other",
+					if l.is_some() { "changed" } else { "deleted" },
+					if r.is_some() { "changed" } else { "deleted" })?;
+				if keep_local { l } else { r }
+			}
+			(None, None) => { None }
+		};
+		merged.extend(result);
+	}
+
+	// SchWiMarks added on both sides may share a name
+	for i in 0..merged.len() {
+		let mut counter: usize = 2;
+		let original: String = merged[i].name.clone();
+		while merged[..i].iter().any(|m| m.name == merged[i].name) {
+			merged[i].name = format!("{} ({})", original, counter);
+			counter += 1;
+		}
+		if merged[i].name != original {
+			println!("renamed SchWiMark \"{}\" to \"{}\" as the name is used twice", original, merged[i].name);
+		}
+	}
+
+	Ok(SyncDocument { mark: merged })
+}
+
+fn merge_mark(base: Option<&SyncedMark>, local: &SyncedMark, remote: &SyncedMark, resolver: &mut Resolver) -> Result<SyncedMark, String> {
+	let mut merge_field = |what: &str, field: fn(&SyncedMark) -> &String| -> Result<String, String> {
+		let (l, r): (&String, &String) = (field(local), field(remote));
+		let b: Option<&String> = base.map(field);
+		if l == r || Some(r) == b { return Ok(l.clone()); }
+		if Some(l) == b { return Ok(r.clone()); }
+		match resolver.keep_local(&local.name, what, l, r)? {
+			true => { Ok(l.clone()) }
+			false => { Ok(r.clone()) }
+		}
+	};
+
+	let name: String = merge_field("name", |m| &m.name)?;
+	let description: String = merge_field("description", |m| &m.description)?;
+	let url: String = merge_field("url/path", |m| &m.url)?;
+	let application: String = merge_field("application", |m| &m.application)?;
+
+	// tags are merged as sets: a tag added or removed on either side is added or removed
+	let base_tags: Vec<String> = base.map(|b| b.tags.clone()).unwrap_or_default();
+	let tags: Vec<String> = local.tags.iter()
+		.chain(remote.tags.iter())
+		.filter(|t| {
+			let (in_local, in_remote, in_base) = (local.tags.contains(t), remote.tags.contains(t), base_tags.contains(t));
+			(in_local && in_remote) || !in_base
+		})
+		.cloned()
+		.collect::<BTreeSet<String>>()
+		.into_iter()
+		.collect();
+
+	Ok(SyncedMark { uuid: local.uuid.clone(), name, description, url, application, tags })
+}
+
+/// Imports the merged SchWiMarks if they differ from the database, after backing it up.
+fn import_changes(database: &Connection, backups: &backup::Backups, current: &SyncDocument, merged: &SyncDocument) -> Result<usize, String> {
+	if current.mark == merged.mark { return Ok(0); }
+	backups.create(database, "pre-sync")?;
+	import(database, current, merged)
+}
+
+/// Applies the difference between the exported and the merged SchWiMarks to the database in a single
+/// transaction. Returns the number of SchWiMarks which were added, changed or deleted.
+fn import(database: &Connection, current: &SyncDocument, merged: &SyncDocument) -> Result<usize, String> {
+	let transaction = database.unchecked_transaction().map_err(|e| e.to_string())?;
+	let mut changed: usize = 0;
+
+	for mark in current.mark.iter().filter(|c| !merged.mark.iter().any(|m| m.uuid == c.uuid)) {
+		if let Some((id, _)) = sql::find_mark_by_uuid(&transaction, &mark.uuid).map_err(|e| e.to_string())? {
+			sql::delete_mark(&transaction, id).map_err(|e| e.to_string())?;
+			changed += 1;
+		}
+	}
+
+	// renamed together, as renaming one after another fails if SchWiMarks swapped their names
+	let mut renames: Vec<(i64, String)> = vec![];
+	for mark in merged.mark.iter() {
+		if !current.mark.iter().any(|c| c.uuid == mark.uuid && c.name != mark.name) { continue; }
+		if let Some((id, _)) = sql::find_mark_by_uuid(&transaction, &mark.uuid).map_err(|e| e.to_string())? {
+			renames.push((id, mark.name.clone()));
+		}
+	}
+	sql::rename_marks(&transaction, &renames).map_err(|e| format!("failed to rename SchWiMarks: {}", e))?;
+
+	for mark in merged.mark.iter() {
+		let current_mark: Option<&SyncedMark> = current.mark.iter().find(|c| c.uuid == mark.uuid);
+		if current_mark == Some(mark) { continue; }
+		changed += 1;
+
+		let (id, trashed): (i64, bool) = match sql::find_mark_by_uuid(&transaction, &mark.uuid).map_err(|e| e.to_string())? {
+			Some(found) => { found }
+			None => {
+				sql::import_mark(&transaction, &mark.uuid, &mark.name, &mark.description, &mark.url, &mark.application, &mark.tags)
+					.map_err(|e| format!("failed to add SchWiMark \"{}\": {}", mark.name, e))?;
+				continue;
+			}
+		};
+		if trashed { sql::restore_mark(&transaction, id).map_err(|e| e.to_string())?; }
+		apply_mark(&transaction, id, mark)
+			.map_err(|e| format!("failed to update SchWiMark \"{}\": {}", mark.name, e))?;
+	}
+
+	transaction.commit().map_err(|e| e.to_string())?;
+	Ok(changed)
+}
+
+fn apply_mark(database: &Connection, id: i64, mark: &SyncedMark) -> rusqlite::Result<()> {
+	let original: sql::SchWiMark = sql::get_mark(database, id)?;
+	if original.name() != mark.name { sql::update_name(database, id, mark.name.clone())?; }
+	if original.description() != mark.description { sql::update_description(database, id, mark.description.clone())?; }
+	if original.url() != mark.url { sql::update_url(database, id, mark.url.clone())?; }
+	if original.application() != mark.application { sql::update_application(database, id, mark.application.clone())?; }
+
+	let original_tags: Vec<String> = sql::get_tags(database, id)?;
+	for tag in original_tags.iter().filter(|t| !mark.tags.contains(t)) {
+		sql::delete_tag(database, id, tag.clone())?;
+	}
+	let added: Vec<String> = mark.tags.iter().filter(|t| !original_tags.contains(t)).cloned().collect();
+	sql::add_tags(database, id, added)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::path::Path;
+	use std::sync::Once;
+	use tempfile::TempDir;
+
+	/// A machine syncing through the shared bare repository, with its own database and working copy.
+	struct Machine {
+		_directory: TempDir,
+		database: Connection,
+		backups: backup::Backups,
+		sync: Sync,
+	}
+
+	impl Machine {
+		fn new(remote: &Path) -> Machine {
+			let directory: TempDir = tempfile::tempdir().unwrap();
+			let mut config: config::Config = config::Config::default();
+			config.database_file = directory.path().join("schwimark.db");
+			config.sync_remote = remote.to_string_lossy().to_string();
+			let backups: backup::Backups = backup::Backups::new(&config);
+			let database: Connection = sql::create_database(&config.database_file, &backups).unwrap();
+			let sync: Sync = Sync::new(&config);
+			Machine { _directory: directory, database, backups, sync }
+		}
+
+		fn sync(&self, resolution: Resolution) -> SyncReport {
+			self.sync.run(&self.database, &self.backups, resolution).unwrap()
+		}
+
+		fn add(&self, name: &str, url: &str) -> i64 {
+			let (_, tags) = sql::add_mark(&self.database, name.to_string(), "".to_string(), url.to_string(), "".to_string(), vec![]).unwrap();
+			tags.markid()
+		}
+
+		fn id(&self, name: &str) -> i64 {
+			sql::get_all_marks(&self.database).unwrap().into_iter().find(|(m, _)| m.name() == name).unwrap().0.id()
+		}
+
+		fn marks(&self) -> Vec<SyncedMark> {
+			export(&self.database).unwrap().mark
+		}
+
+		fn mark(&self, name: &str) -> SyncedMark {
+			self.marks().into_iter().find(|m| m.name == name).unwrap()
+		}
+	}
+
+	fn remote() -> TempDir {
+		static GIT_IDENTITY: Once = Once::new();
+		GIT_IDENTITY.call_once(|| {
+			for (key, value) in [
+				("GIT_AUTHOR_NAME", "schwimark"),
+				("GIT_AUTHOR_EMAIL", "schwimark@localhost"),
+				("GIT_COMMITTER_NAME", "schwimark"),
+				("GIT_COMMITTER_EMAIL", "schwimark@localhost"),
+				("GIT_CONFIG_NOSYSTEM", "1"),
+			] {
+				std::env::set_var(key, value);
+			}
+		});
+		let remote: TempDir = tempfile::tempdir().unwrap();
+		let status = Command::new("git").args(["init", "--quiet", "--bare"]).arg(remote.path()).status().unwrap();
+		assert!(status.success());
+		remote
+	}
+
+	/// Two machines which both have the SchWiMark "docs" synced.
+	fn synced_pair(remote: &Path) -> (Machine, Machine) {
+		let (a, b): (Machine, Machine) = (Machine::new(remote), Machine::new(remote));
+		a.add("docs", "https://docs.example.com");
+		a.sync(Resolution::Ask);
+		b.sync(Resolution::Ask);
+		assert_eq!(a.marks(), b.marks());
+		(a, b)
+	}
+
+	#[test]
+	fn fast_forward() {
+		let remote: TempDir = remote();
+		let (a, b): (Machine, Machine) = synced_pair(remote.path());
+
+		sql::update_description(&a.database, a.id("docs"), "the documentation".to_string()).unwrap();
+		sql::add_tags(&a.database, a.id("docs"), vec!["work".to_string()]).unwrap();
+		let report: SyncReport = a.sync(Resolution::Ask);
+		assert!(report.committed && report.pushed && !report.merged);
+
+		let report: SyncReport = b.sync(Resolution::Ask);
+		assert!(!report.committed && report.merged && !report.pushed);
+		assert_eq!(report.conflicts, 0);
+		assert_eq!(b.mark("docs").description, "the documentation");
+		assert_eq!(b.mark("docs").tags, ["work"]);
+		assert_eq!(a.marks(), b.marks());
+	}
+
+	#[test]
+	fn diverged_three_way_merge() {
+		let remote: TempDir = remote();
+		let (a, b): (Machine, Machine) = synced_pair(remote.path());
+
+		sql::update_description(&a.database, a.id("docs"), "from a".to_string()).unwrap();
+		sql::add_tags(&a.database, a.id("docs"), vec!["a".to_string()]).unwrap();
+		a.add("only a", "https://a.example.com");
+		a.sync(Resolution::Ask);
+
+		sql::update_url(&b.database, b.id("docs"), "https://docs.example.org".to_string()).unwrap();
+		sql::add_tags(&b.database, b.id("docs"), vec!["b".to_string()]).unwrap();
+		b.add("only b", "https://b.example.com");
+		let report: SyncReport = b.sync(Resolution::Ask);
+		assert!(report.committed && report.merged && report.pushed);
+		assert_eq!(report.conflicts, 0);
+
+		let docs: SyncedMark = b.mark("docs");
+		assert_eq!((docs.description.as_str(), docs.url.as_str()), ("from a", "https://docs.example.org"));
+		assert_eq!(docs.tags, ["a", "b"]);
+		assert_eq!(b.marks().len(), 3);
+
+		a.sync(Resolution::Ask);
+		assert_eq!(a.marks(), b.marks());
+	}
+
+	#[test]
+	fn field_conflict_resolved_with_prefer() {
+		let remote: TempDir = remote();
+		let (a, b): (Machine, Machine) = synced_pair(remote.path());
+
+		sql::update_description(&a.database, a.id("docs"), "from a".to_string()).unwrap();
+		a.sync(Resolution::Ask);
+		sql::update_description(&b.database, b.id("docs"), "from b".to_string()).unwrap();
+		sql::update_url(&b.database, b.id("docs"), "https://docs.example.org".to_string()).unwrap();
+
+		let report: SyncReport = b.sync(Resolution::Remote);
+		assert_eq!(report.conflicts, 1);
+		assert_eq!(b.mark("docs").description, "from a");
+		assert_eq!(b.mark("docs").url, "https://docs.example.org");
+
+		a.sync(Resolution::Ask);
+		assert_eq!(a.marks(), b.marks());
+	}
+
+	#[test]
+	fn delete_and_change_conflict() {
+		let remote: TempDir = remote();
+		let (a, b): (Machine, Machine) = synced_pair(remote.path());
+
+		sql::delete_mark(&a.database, a.id("docs")).unwrap();
+		a.sync(Resolution::Ask);
+		assert!(a.marks().is_empty());
+		sql::update_description(&b.database, b.id("docs"), "still needed".to_string()).unwrap();
+
+		let report: SyncReport = b.sync(Resolution::Local);
+		assert_eq!(report.conflicts, 1);
+		assert_eq!(b.mark("docs").description, "still needed");
+
+		// the deleted SchWiMark is restored from the trash
+		a.sync(Resolution::Ask);
+		assert_eq!(a.marks(), b.marks());
+		assert_eq!(sql::get_trash(&a.database).unwrap().len(), 0);
+	}
+
+	#[test]
+	fn swapped_names() {
+		let remote: TempDir = remote();
+		let (a, b): (Machine, Machine) = synced_pair(remote.path());
+		a.add("api", "https://api.example.com");
+		a.sync(Resolution::Ask);
+		b.sync(Resolution::Ask);
+
+		let (docs, api): (i64, i64) = (a.id("docs"), a.id("api"));
+		sql::rename_marks(&a.database, &[(docs, "api".to_string()), (api, "docs".to_string())]).unwrap();
+		a.sync(Resolution::Ask);
+
+		b.sync(Resolution::Ask);
+		assert_eq!(b.mark("docs").url, "https://api.example.com");
+		assert_eq!(b.mark("api").url, "https://docs.example.com");
+		assert_eq!(a.marks(), b.marks());
+	}
+}
@@ -0,0 +1,694 @@
+use crate::sql;
+
+use rusqlite::Connection;
+use ratatui::DefaultTerminal;
+use ratatui::Frame;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Position, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState, Wrap};
+
+#[derive(Clone, Copy, PartialEq)]
+enum SortColumn {
+	Id,
+	Name,
+	Url,
+	Application,
+	Tags,
+}
+
+impl SortColumn {
+	fn next(self) -> SortColumn {
+		match self {
+			SortColumn::Id => { SortColumn::Name }
+			SortColumn::Name => { SortColumn::Url }
+			SortColumn::Url => { SortColumn::Application }
+			SortColumn::Application => { SortColumn::Tags }
+			SortColumn::Tags => { SortColumn::Id }
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+	Name,
+	Description,
+	Url,
+	Application,
+}
+
+impl Field {
+	fn label(self) -> &'static str {
+		match self {
+			Field::Name => { "name" }
+			Field::Description => { "description" }
+			Field::Url => { "url/path" }
+			Field::Application => { "application" }
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+	Browse,
+	Filter,
+	Edit(Field),
+	AddTag,
+	RemoveTag,
+}
+
+/// A single line text input with a cursor counted in characters.
+#[derive(Default)]
+struct Input {
+	buffer: String,
+	cursor: usize,
+}
+
+impl Input {
+	fn set(&mut self, value: &str) {
+		self.buffer = value.to_string();
+		self.cursor = self.buffer.chars().count();
+	}
+
+	fn byte_index(&self) -> usize {
+		self.buffer.char_indices().nth(self.cursor).map(|(i, _)| i).unwrap_or(self.buffer.len())
+	}
+
+	fn insert(&mut self, c: char) {
+		let i: usize = self.byte_index();
+		self.buffer.insert(i, c);
+		self.cursor += 1;
+	}
+
+	fn backspace(&mut self) {
+		if self.cursor == 0 { return; }
+		self.cursor -= 1;
+		let i: usize = self.byte_index();
+		self.buffer.remove(i);
+	}
+
+	fn delete(&mut self) {
+		if self.cursor >= self.buffer.chars().count() { return; }
+		let i: usize = self.byte_index();
+		self.buffer.remove(i);
+	}
+
+	fn handle_key(&mut self, code: KeyCode) {
+		match code {
+			KeyCode::Char(c) => { self.insert(c); }
+			KeyCode::Backspace => { self.backspace(); }
+			KeyCode::Delete => { self.delete(); }
+			KeyCode::Left => { self.cursor = self.cursor.saturating_sub(1); }
+			KeyCode::Right => { self.cursor = (self.cursor + 1).min(self.buffer.chars().count()); }
+			KeyCode::Home => { self.cursor = 0; }
+			KeyCode::End => { self.cursor = self.buffer.chars().count(); }
+			_ => {}
+		}
+	}
+}
+
+struct App<'a> {
+	database: &'a Connection,
+	marks: Vec<(sql::SchWiMark, sql::Tag)>,
+	all_tags: Vec<String>,
+	visible: Vec<usize>,
+	table_state: TableState,
+	sort: SortColumn,
+	sort_reverse: bool,
+	filter: String,
+	mode: Mode,
+	input: Input,
+	completions: Vec<String>,
+	completion_index: usize,
+	status: String,
+	quit: bool,
+}
+
+/// Opens a full-screen manager listing all SchWiMarks which allows sorting, filtering, editing
+/// fields and tags as well as deleting SchWiMarks in place. Every change can be undone.
+pub fn start_tui(database: &Connection) -> Result<(), String> {
+	let mut app: App = App::new(database)?;
+	let mut terminal: DefaultTerminal = ratatui::init();
+	let result: Result<(), String> = app.run(&mut terminal);
+	ratatui::restore();
+	result
+}
+
+impl<'a> App<'a> {
+	fn new(database: &'a Connection) -> Result<App<'a>, String> {
+		let mut app: App = App {
+			database,
+			marks: vec![],
+			all_tags: vec![],
+			visible: vec![],
+			table_state: TableState::default(),
+			sort: SortColumn::Id,
+			sort_reverse: false,
+			filter: String::new(),
+			mode: Mode::Browse,
+			input: Input::default(),
+			completions: vec![],
+			completion_index: 0,
+			status: String::new(),
+			quit: false,
+		};
+		app.reload()?;
+		Ok(app)
+	}
+
+	fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), String> {
+		while !self.quit {
+			terminal.draw(|frame| self.draw(frame)).map_err(|e| e.to_string())?;
+			match event::read().map_err(|e| e.to_string())? {
+				Event::Key(key) if key.kind == KeyEventKind::Press => { self.handle_key(key); }
+				_ => {}
+			}
+		}
+		Ok(())
+	}
+
+	fn reload(&mut self) -> Result<(), String> {
+		let selected_id: Option<i64> = self.selected().map(|(m, _)| m.id());
+		self.marks = sql::get_all_marks(self.database).map_err(|e| e.to_string())?;
+		self.all_tags = sql::get_all_tags(self.database).map_err(|e| e.to_string())?;
+		self.refresh_visible(selected_id);
+		Ok(())
+	}
+
+	fn refresh_visible(&mut self, selected_id: Option<i64>) {
+		let filter: String = self.filter.to_lowercase();
+		self.visible = (0..self.marks.len())
+			.filter(|i| {
+				let (mark, tags) = &self.marks[*i];
+				filter.is_empty()
+					|| mark.name().to_lowercase().contains(&filter)
+					|| mark.description().to_lowercase().contains(&filter)
+					|| mark.url().to_lowercase().contains(&filter)
+					|| tags.tags().iter().any(|t| t.to_lowercase().contains(&filter))
+			})
+			.collect();
+
+		let marks: &Vec<(sql::SchWiMark, sql::Tag)> = &self.marks;
+		let sort: SortColumn = self.sort;
+		self.visible.sort_by(|a, b| {
+			let (ma, ta) = &marks[*a];
+			let (mb, tb) = &marks[*b];
+			match sort {
+				SortColumn::Id => { ma.id().cmp(&mb.id()) }
+				SortColumn::Name => { ma.name().to_lowercase().cmp(&mb.name().to_lowercase()) }
+				SortColumn::Url => { ma.url().cmp(mb.url()) }
+				SortColumn::Application => { ma.application().cmp(mb.application()) }
+				SortColumn::Tags => { ta.tags().join(",").cmp(&tb.tags().join(",")) }
+			}
+		});
+		if self.sort_reverse { self.visible.reverse(); }
+
+		let position: Option<usize> = selected_id
+			.and_then(|id| self.visible.iter().position(|i| self.marks[*i].0.id() == id));
+		match (position, self.visible.is_empty()) {
+			(_, true) => { self.table_state.select(None); }
+			(Some(p), false) => { self.table_state.select(Some(p)); }
+			(None, false) => {
+				let p: usize = self.table_state.selected().unwrap_or(0).min(self.visible.len() - 1);
+				self.table_state.select(Some(p));
+			}
+		}
+	}
+
+	fn selected(&self) -> Option<&(sql::SchWiMark, sql::Tag)> {
+		self.table_state.selected()
+			.and_then(|i| self.visible.get(i))
+			.map(|i| &self.marks[*i])
+	}
+
+	fn handle_key(&mut self, key: KeyEvent) {
+		// the terminal is in raw mode, so Ctrl-C arrives as a key instead of a signal
+		if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+			self.quit = true;
+			return;
+		}
+		if key.code != KeyCode::Tab { self.completions.clear(); }
+		match self.mode {
+			Mode::Browse => { self.handle_browse_key(key.code); }
+			Mode::Filter => {
+				match key.code {
+					KeyCode::Enter => { self.mode = Mode::Browse; }
+					KeyCode::Esc => {
+						self.filter.clear();
+						self.mode = Mode::Browse;
+					}
+					code => {
+						self.input.handle_key(code);
+						self.filter = self.input.buffer.clone();
+					}
+				}
+				let selected_id: Option<i64> = self.selected().map(|(m, _)| m.id());
+				self.refresh_visible(selected_id);
+			}
+			Mode::Edit(_) | Mode::AddTag | Mode::RemoveTag => {
+				match key.code {
+					KeyCode::Enter => {
+						self.submit();
+						self.mode = Mode::Browse;
+					}
+					KeyCode::Esc => { self.mode = Mode::Browse; }
+					KeyCode::Tab => { self.complete(); }
+					code => { self.input.handle_key(code); }
+				}
+			}
+		}
+	}
+
+	fn handle_browse_key(&mut self, code: KeyCode) {
+		self.status.clear();
+		let len: usize = self.visible.len();
+		let current: usize = self.table_state.selected().unwrap_or(0);
+		let last: usize = len.saturating_sub(1);
+		match code {
+			KeyCode::Char('q') => { self.quit = true; }
+			KeyCode::Esc => {
+				if self.filter.is_empty() { self.quit = true; return; }
+				self.filter.clear();
+				let selected_id: Option<i64> = self.selected().map(|(m, _)| m.id());
+				self.refresh_visible(selected_id);
+			}
+			KeyCode::Char('j') | KeyCode::Down | KeyCode::Char('k') | KeyCode::Up | KeyCode::PageDown | KeyCode::PageUp
+				| KeyCode::Char('g') | KeyCode::Home | KeyCode::Char('G') | KeyCode::End if len == 0 => {}
+			KeyCode::Char('j') | KeyCode::Down => { self.table_state.select(Some((current + 1).min(last))); }
+			KeyCode::Char('k') | KeyCode::Up => { self.table_state.select(Some(current.saturating_sub(1))); }
+			KeyCode::PageDown => { self.table_state.select(Some((current + 10).min(last))); }
+			KeyCode::PageUp => { self.table_state.select(Some(current.saturating_sub(10))); }
+			KeyCode::Char('g') | KeyCode::Home => { self.table_state.select(Some(0)); }
+			KeyCode::Char('G') | KeyCode::End => { self.table_state.select(Some(last)); }
+			KeyCode::Char('s') => {
+				self.sort = self.sort.next();
+				let selected_id: Option<i64> = self.selected().map(|(m, _)| m.id());
+				self.refresh_visible(selected_id);
+			}
+			KeyCode::Char('r') => {
+				self.sort_reverse = !self.sort_reverse;
+				let selected_id: Option<i64> = self.selected().map(|(m, _)| m.id());
+				self.refresh_visible(selected_id);
+			}
+			KeyCode::Char('/') => {
+				let filter: String = self.filter.clone();
+				self.input.set(&filter);
+				self.mode = Mode::Filter;
+			}
+			KeyCode::Char('n') => { self.start_edit(Field::Name); }
+			KeyCode::Char('d') => { self.start_edit(Field::Description); }
+			KeyCode::Char('u') => { self.start_edit(Field::Url); }
+			KeyCode::Char('a') => { self.start_edit(Field::Application); }
+			KeyCode::Char('+') => {
+				if self.selected().is_none() { return; }
+				self.input.set("");
+				self.mode = Mode::AddTag;
+			}
+			KeyCode::Char('-') => {
+				if self.selected().is_none() { return; }
+				self.input.set("");
+				self.mode = Mode::RemoveTag;
+			}
+			KeyCode::Char('x') | KeyCode::Delete => { self.delete_selected(); }
+			KeyCode::Char('z') => { self.undo(); }
+			_ => {}
+		}
+	}
+
+	fn start_edit(&mut self, field: Field) {
+		let value: String = match self.selected() {
+			Some((mark, _)) => {
+				match field {
+					Field::Name => { mark.name().to_string() }
+					Field::Description => { mark.description().to_string() }
+					Field::Url => { mark.url().to_string() }
+					Field::Application => { mark.application().to_string() }
+				}
+			}
+			None => { return; }
+		};
+		self.input.set(&value);
+		self.mode = Mode::Edit(field);
+	}
+
+	fn submit(&mut self) {
+		let id: i64 = match self.selected() {
+			Some((mark, _)) => { mark.id() }
+			None => { return; }
+		};
+		let mut value: String = self.input.buffer.clone();
+		value.retain(|c| !sql::FORBIDDEN_CHARACTERS.contains(c));
+		let value: String = value.trim().to_string();
+
+		let result: rusqlite::Result<()> = match self.mode {
+			Mode::Edit(Field::Name) if value.is_empty() => { self.status = "name cannot be empty".to_string(); return; }
+			Mode::Edit(Field::Url) if value.is_empty() => { self.status = "url cannot be empty".to_string(); return; }
+			Mode::Edit(Field::Name) => { sql::update_name(self.database, id, value) }
+			Mode::Edit(Field::Description) => { sql::update_description(self.database, id, value) }
+			Mode::Edit(Field::Url) => { sql::update_url(self.database, id, value) }
+			Mode::Edit(Field::Application) => { sql::update_application(self.database, id, value) }
+			Mode::AddTag => {
+				if value.is_empty() { return; }
+				if self.selected().is_some_and(|(_, t)| t.tags().contains(&value)) {
+					self.status = "tag is already selected for this SchWiMark".to_string();
+					return;
+				}
+				sql::add_tags(self.database, id, vec![value])
+			}
+			Mode::RemoveTag => {
+				if value.is_empty() { return; }
+				sql::delete_tag(self.database, id, value)
+			}
+			Mode::Browse | Mode::Filter => { return; }
+		};
+
+		match result {
+			Ok(_) => { self.status = "saved".to_string(); }
+			Err(e) => { self.status = format!("sql failed with error: \"{}\"", e); }
+		}
+		if let Err(e) = self.reload() { self.status = e; }
+	}
+
+	/// Completes the current input from the existing tags, cycling through the candidates on
+	/// repeated presses.
+	fn complete(&mut self) {
+		if self.completions.is_empty() {
+			let mark_tags: Vec<String> = self.selected().map(|(_, t)| t.tags().to_vec()).unwrap_or_default();
+			let source: Vec<String> = match self.mode {
+				Mode::AddTag => {
+					self.all_tags.iter().filter(|t| !mark_tags.contains(t)).cloned().collect()
+				}
+				Mode::RemoveTag => { mark_tags }
+				_ => { return; }
+			};
+			let prefix: &str = &self.input.buffer;
+			self.completions = source.into_iter().filter(|t| t.starts_with(prefix)).collect();
+			self.completion_index = 0;
+		} else {
+			self.completion_index = (self.completion_index + 1) % self.completions.len();
+		}
+
+		if let Some(completion) = self.completions.get(self.completion_index) {
+			let completion: String = completion.clone();
+			self.input.set(&completion);
+		}
+	}
+
+	fn delete_selected(&mut self) {
+		let (id, name): (i64, String) = match self.selected() {
+			Some((mark, _)) => { (mark.id(), mark.name().to_string()) }
+			None => { return; }
+		};
+
+		match sql::delete_mark(self.database, id) {
+			Ok(_) => { self.status = format!("moved \"{}\" to the trash (press z to undo)", name); }
+			Err(e) => { self.status = format!("sql failed with error: \"{}\"", e); }
+		}
+		if let Err(e) = self.reload() { self.status = e; }
+	}
+
+	fn undo(&mut self) {
+		match sql::undo(self.database) {
+			Ok(Some(undone)) => {
+				self.status = match undone.renamed.first() {
+					Some((name, new_name)) => { format!("undid {}, restored \"{}\" as \"{}\" as its name is taken", undone.operation, name, new_name) }
+					None => { format!("undid {} of {} SchWiMark(s)", undone.operation, undone.count) }
+				};
+			}
+			Ok(None) => { self.status = "nothing to undo".to_string(); }
+			Err(e) => { self.status = format!("sql failed with error: \"{}\"", e); }
+		}
+		if let Err(e) = self.reload() { self.status = e; }
+	}
+
+	fn draw(&mut self, frame: &mut Frame) {
+		let [main_area, input_area, help_area] = Layout::vertical([
+			Constraint::Min(3),
+			Constraint::Length(1),
+			Constraint::Length(1),
+		]).areas(frame.area());
+		let [table_area, detail_area] = Layout::horizontal([
+			Constraint::Percentage(60),
+			Constraint::Percentage(40),
+		]).areas(main_area);
+
+		self.draw_table(frame, table_area);
+		self.draw_detail(frame, detail_area);
+		self.draw_input(frame, input_area);
+
+		let help: String = match self.mode {
+			Mode::Browse => {
+				"q quit  / filter  s sort  r reverse  n/d/u/a edit  + add tag  - remove tag  x delete  z undo".to_string()
+			}
+			Mode::Filter => { "enter keep filter  esc clear filter".to_string() }
+			_ if !self.completions.is_empty() => { self.completions.join("  ") }
+			Mode::AddTag | Mode::RemoveTag => { "enter save  esc cancel  tab complete".to_string() }
+			Mode::Edit(_) => { "enter save  esc cancel".to_string() }
+		};
+		frame.render_widget(Paragraph::new(help).style(Style::default().add_modifier(Modifier::DIM)), help_area);
+	}
+
+	fn draw_table(&mut self, frame: &mut Frame, area: Rect) {
+		let arrow: &str = if self.sort_reverse { " ▼" } else { " ▲" };
+		let header_cell = |column: SortColumn, title: &str| -> Cell {
+			match self.sort == column {
+				true => { Cell::from(format!("{}{}", title, arrow)) }
+				false => { Cell::from(title.to_string()) }
+			}
+		};
+		let header: Row = Row::new(vec![
+			header_cell(SortColumn::Id, "id"),
+			header_cell(SortColumn::Name, "name"),
+			header_cell(SortColumn::Url, "url"),
+			header_cell(SortColumn::Application, "application"),
+			header_cell(SortColumn::Tags, "tags"),
+		]).style(Style::default().add_modifier(Modifier::BOLD));
+
+		let rows: Vec<Row> = self.visible.iter().map(|i| {
+			let (mark, tags) = &self.marks[*i];
+			Row::new(vec![
+				Cell::from(mark.id().to_string()),
+				Cell::from(mark.name().to_string()),
+				Cell::from(mark.url().to_string()),
+				Cell::from(mark.application().to_string()),
+				Cell::from(tags.tags().join(", ")),
+			])
+		}).collect();
+
+		let title: String = match self.filter.is_empty() {
+			true => { format!(" SchWiMarks ({}) ", self.visible.len()) }
+			false => { format!(" SchWiMarks ({}/{}) filter: {} ", self.visible.len(), self.marks.len(), self.filter) }
+		};
+		let table: Table = Table::new(rows, [
+			Constraint::Length(4),
+			Constraint::Percentage(25),
+			Constraint::Percentage(35),
+			Constraint::Percentage(15),
+			Constraint::Percentage(25),
+		])
+			.header(header)
+			.block(Block::default().borders(Borders::ALL).title(title))
+			.row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+		frame.render_stateful_widget(table, area, &mut self.table_state);
+	}
+
+	fn draw_detail(&self, frame: &mut Frame, area: Rect) {
+		let lines: Vec<Line> = match self.selected() {
+			Some((mark, tags)) => {
+				vec![
+					Line::from(format!("id: {}", mark.id())),
+					Line::from(format!("name: {}", mark.name())),
+					Line::from(format!("description: {}", mark.description())),
+					Line::from(format!("url/path: {}", mark.url())),
+					Line::from(format!("application: {}", mark.application())),
+					Line::from(format!("tags: {}", tags.tags().join(", "))),
+				]
+			}
+			None => { vec![Line::from("no SchWiMark selected")] }
+		};
+		let detail: Paragraph = Paragraph::new(lines)
+			.wrap(Wrap { trim: false })
+			.block(Block::default().borders(Borders::ALL).title(" details "));
+		frame.render_widget(detail, area);
+	}
+
+	fn draw_input(&self, frame: &mut Frame, area: Rect) {
+		let prompt: String = match self.mode {
+			Mode::Browse => {
+				frame.render_widget(Paragraph::new(self.status.as_str()), area);
+				return;
+			}
+			Mode::Filter => { "filter> ".to_string() }
+			Mode::Edit(field) => { format!("{}> ", field.label()) }
+			Mode::AddTag => { "add tag> ".to_string() }
+			Mode::RemoveTag => { "remove tag> ".to_string() }
+		};
+		let prompt_width: u16 = prompt.chars().count() as u16;
+		frame.render_widget(Paragraph::new(format!("{}{}", prompt, self.input.buffer)), area);
+		frame.set_cursor_position(Position::new(
+			(area.x + prompt_width + self.input.cursor as u16).min(area.right().saturating_sub(1)),
+			area.y,
+		));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::backup;
+	use crate::config;
+	use std::path::PathBuf;
+
+	fn database() -> Connection {
+		let database: Connection = sql::create_database(&PathBuf::from(":memory:"), &backup::Backups::new(&config::Config::default())).unwrap();
+		for (name, description, url, tags) in [
+			("docs", "the Rust docs", "https://doc.rust-lang.org", vec!["rust", "work"]),
+			("Blog", "", "https://example.com/blog", vec![]),
+			("api", "", "https://api.example.com", vec!["work"]),
+		] {
+			let tags: Vec<String> = tags.into_iter().map(|t| t.to_string()).collect();
+			sql::add_mark(&database, name.to_string(), description.to_string(), url.to_string(), "".to_string(), tags).unwrap();
+		}
+		database
+	}
+
+	fn press(app: &mut App, code: KeyCode) {
+		app.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+	}
+
+	fn type_text(app: &mut App, text: &str) {
+		for c in text.chars() { press(app, KeyCode::Char(c)); }
+	}
+
+	fn visible_names(app: &App) -> Vec<String> {
+		app.visible.iter().map(|i| app.marks[*i].0.name().to_string()).collect()
+	}
+
+	fn selected_name(app: &App) -> Option<String> {
+		app.selected().map(|(m, _)| m.name().to_string())
+	}
+
+	#[test]
+	fn filter_matches_names_descriptions_urls_and_tags_ignoring_case() {
+		let database: Connection = database();
+		let mut app: App = App::new(&database).unwrap();
+		press(&mut app, KeyCode::Char('/'));
+		type_text(&mut app, "RUST");
+		assert_eq!(visible_names(&app), ["docs"]);
+		press(&mut app, KeyCode::Enter);
+
+		press(&mut app, KeyCode::Char('/'));
+		for _ in 0..4 { press(&mut app, KeyCode::Backspace); }
+		type_text(&mut app, "work");
+		assert_eq!(visible_names(&app), ["docs", "api"]);
+		press(&mut app, KeyCode::Esc);
+		assert_eq!(visible_names(&app), ["docs", "Blog", "api"]);
+
+		press(&mut app, KeyCode::Char('/'));
+		type_text(&mut app, "nothing");
+		press(&mut app, KeyCode::Enter);
+		assert!(app.visible.is_empty() && app.selected().is_none());
+		press(&mut app, KeyCode::Char('j'));
+		// the first Esc clears the filter, the second one quits
+		press(&mut app, KeyCode::Esc);
+		assert!(!app.quit && app.visible.len() == 3);
+		press(&mut app, KeyCode::Esc);
+		assert!(app.quit);
+	}
+
+	#[test]
+	fn sorting_keeps_the_selected_mark() {
+		let database: Connection = database();
+		let mut app: App = App::new(&database).unwrap();
+		press(&mut app, KeyCode::Char('j'));
+		assert_eq!(selected_name(&app).as_deref(), Some("Blog"));
+
+		press(&mut app, KeyCode::Char('s'));
+		assert!(app.sort == SortColumn::Name);
+		assert_eq!(visible_names(&app), ["api", "Blog", "docs"]);
+		assert_eq!(selected_name(&app).as_deref(), Some("Blog"));
+		press(&mut app, KeyCode::Char('s'));
+		assert_eq!(visible_names(&app), ["api", "docs", "Blog"]);
+		press(&mut app, KeyCode::Char('r'));
+		assert_eq!(visible_names(&app), ["Blog", "docs", "api"]);
+		assert_eq!(selected_name(&app).as_deref(), Some("Blog"));
+
+		press(&mut app, KeyCode::Char('G'));
+		press(&mut app, KeyCode::Char('j'));
+		assert_eq!(selected_name(&app).as_deref(), Some("api"));
+		press(&mut app, KeyCode::PageUp);
+		assert_eq!(selected_name(&app).as_deref(), Some("Blog"));
+	}
+
+	#[test]
+	fn edits_are_saved_without_forbidden_characters() {
+		let database: Connection = database();
+		let mut app: App = App::new(&database).unwrap();
+		press(&mut app, KeyCode::Char('d'));
+		press(&mut app, KeyCode::End);
+		type_text(&mut app, "\tand\nmore\0 ");
+		press(&mut app, KeyCode::Enter);
+		assert_eq!(app.status, "saved");
+		assert!(app.mode == Mode::Browse);
+		assert_eq!(app.selected().unwrap().0.description(), "the Rust docsandmore");
+
+		press(&mut app, KeyCode::Char('n'));
+		press(&mut app, KeyCode::Home);
+		for _ in 0..4 { press(&mut app, KeyCode::Delete); }
+		type_text(&mut app, "\t");
+		press(&mut app, KeyCode::Enter);
+		assert_eq!(app.status, "name cannot be empty");
+		assert_eq!(selected_name(&app).as_deref(), Some("docs"));
+	}
+
+	#[test]
+	fn tags_are_completed_and_not_added_twice() {
+		let database: Connection = database();
+		let mut app: App = App::new(&database).unwrap();
+		press(&mut app, KeyCode::Char('j'));
+		press(&mut app, KeyCode::Char('+'));
+		press(&mut app, KeyCode::Tab);
+		assert_eq!(app.input.buffer, "rust");
+		press(&mut app, KeyCode::Tab);
+		assert_eq!(app.input.buffer, "work");
+		press(&mut app, KeyCode::Enter);
+		assert_eq!(app.selected().unwrap().1.tags(), ["work"]);
+
+		press(&mut app, KeyCode::Char('+'));
+		type_text(&mut app, "work");
+		press(&mut app, KeyCode::Enter);
+		assert_eq!(app.status, "tag is already selected for this SchWiMark");
+
+		press(&mut app, KeyCode::Char('-'));
+		press(&mut app, KeyCode::Tab);
+		press(&mut app, KeyCode::Enter);
+		assert!(app.selected().unwrap().1.tags().is_empty());
+	}
+
+	#[test]
+	fn delete_and_undo_reload_the_marks() {
+		let database: Connection = database();
+		let mut app: App = App::new(&database).unwrap();
+		press(&mut app, KeyCode::Char('x'));
+		assert_eq!(app.status, "moved \"docs\" to the trash (press z to undo)");
+		assert_eq!(visible_names(&app), ["Blog", "api"]);
+		press(&mut app, KeyCode::Char('z'));
+		assert_eq!(app.status, "undid delete of 1 SchWiMark(s)");
+		assert_eq!(visible_names(&app), ["docs", "Blog", "api"]);
+	}
+
+	#[test]
+	fn ctrl_c_quits_in_every_mode() {
+		let database: Connection = database();
+		for mode in [None, Some('/'), Some('n'), Some('+')] {
+			let mut app: App = App::new(&database).unwrap();
+			if let Some(key) = mode { press(&mut app, KeyCode::Char(key)); }
+			let input: String = app.input.buffer.clone();
+			app.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL));
+			assert!(app.quit);
+			assert_eq!(app.input.buffer, input);
+		}
+	}
+}